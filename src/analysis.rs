@@ -0,0 +1,265 @@
+//! Levels and spectrum of what plays. Every sound feeds a `Tap` with the
+//! samples it decodes, mixed down to mono, and the interface reads the
+//! latest window of them each time it redraws.
+
+use std::{collections::VecDeque, f32::consts::PI, sync::{Arc, Mutex}, time::Duration};
+use rodio::Source;
+
+/// Samples kept by a tap and analysed at once. A power of two, for the FFT.
+pub const WINDOW: usize = 1024;
+/// Level shown as silence by meters.
+pub const FLOOR_DB: f32 = -60.0;
+/// Level of the weakest band the spectrum shows.
+const SPECTRUM_FLOOR_DB: f32 = -72.0;
+/// Frequencies covered by the spectrum.
+const SPECTRUM_RANGE: (f32, f32) = (40.0, 16000.0);
+/// Samples gathered before they are handed to the tap, so the audio thread
+/// does not lock it for every sample.
+const FLUSH: usize = 256;
+
+#[derive(Default)]
+struct TapBuffer {
+    samples: VecDeque<f32>,
+    sample_rate: u32,
+}
+
+/// The latest samples of a source, shared between the thread decoding it and
+/// the interface.
+#[derive(Clone, Default)]
+pub struct Tap {
+    buffer: Arc<Mutex<TapBuffer>>,
+}
+
+impl Tap {
+    pub fn new() -> Self {
+        Tap::default()
+    }
+
+    fn push(&self, samples: &[f32], sample_rate: u32) {
+        let Ok(mut buffer) = self.buffer.lock() else { return };
+        buffer.sample_rate = sample_rate;
+        buffer.samples.extend(samples);
+        let excess = buffer.samples.len().saturating_sub(WINDOW);
+        buffer.samples.drain(..excess);
+    }
+
+    /// The last `WINDOW` samples, oldest first, padded with silence at the
+    /// start until that many were decoded.
+    pub fn window(&self) -> Vec<f32> {
+        let mut window = vec![0.0; WINDOW];
+        if let Ok(buffer) = self.buffer.lock() {
+            let start = WINDOW - buffer.samples.len();
+            for (slot, sample) in window[start..].iter_mut().zip(&buffer.samples) {
+                *slot = *sample;
+            }
+        }
+        window
+    }
+
+    /// Sample rate of the samples, 0 before any was decoded.
+    pub fn get_sample_rate(&self) -> u32 {
+        self.buffer.lock().map_or(0, |buffer| buffer.sample_rate)
+    }
+}
+
+/// Passes the samples of `source` through unchanged, copying them mixed
+/// down to mono into a tap.
+pub struct TapSource<S> {
+    source: S,
+    tap: Tap,
+    frame: f32,
+    channel: u16,
+    pending: Vec<f32>,
+}
+
+impl<S: Source<Item = f32>> TapSource<S> {
+    pub fn new(source: S, tap: Tap) -> Self {
+        TapSource { source, tap, frame: 0.0, channel: 0, pending: Vec::with_capacity(FLUSH) }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for TapSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.source.next()?;
+        let channels = self.source.channels().max(1);
+        self.frame += sample;
+        self.channel += 1;
+        if self.channel >= channels {
+            self.pending.push(self.frame / channels as f32);
+            self.frame = 0.0;
+            self.channel = 0;
+            if self.pending.len() >= FLUSH {
+                self.tap.push(&self.pending, self.source.sample_rate());
+                self.pending.clear();
+            }
+        }
+        Some(sample)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for TapSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+/// Loudness of a window of samples, as amplitudes from 0 to 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Levels {
+    pub rms: f32,
+    pub peak: f32,
+}
+
+impl Levels {
+    pub fn measure(samples: &[f32]) -> Self {
+        if samples.is_empty() {
+            return Levels::default();
+        }
+        let sum: f32 = samples.iter().map(|sample| sample * sample).sum();
+        let peak = samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        Levels { rms: (sum / samples.len() as f32).sqrt().min(1.0), peak: peak.min(1.0) }
+    }
+}
+
+/// Where `level` falls between `FLOOR_DB` and 0 dB, from 0 to 1.
+pub fn db_fraction(level: f32) -> f32 {
+    fraction(level, FLOOR_DB)
+}
+
+fn fraction(level: f32, floor: f32) -> f32 {
+    if level <= 0.0 {
+        return 0.0;
+    }
+    ((20.0 * level.log10() - floor) / -floor).clamp(0.0, 1.0)
+}
+
+/// In-place radix-2 FFT. Both slices have the same power of two length.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let (re_b, im_b) = (re[b] * cos - im[b] * sin, re[b] * sin + im[b] * cos);
+                re[b] = re[a] - re_b;
+                im[b] = im[a] - im_b;
+                re[a] += re_b;
+                im[a] += im_b;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Strength of `bands` frequency bands of `samples`, spaced evenly on a
+/// logarithmic scale, each from 0 to 1. `samples` holds `WINDOW` samples.
+pub fn spectrum(samples: &[f32], sample_rate: u32, bands: usize) -> Vec<f32> {
+    let n = samples.len();
+    if bands == 0 || sample_rate == 0 || n < 2 || !n.is_power_of_two() {
+        return vec![0.0; bands];
+    }
+    // Hann window, which halves the amplitude of a steady tone
+    let mut re: Vec<f32> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, sample)| sample * 0.5 * (1.0 - (2.0 * PI * i as f32 / (n - 1) as f32).cos()))
+        .collect();
+    let mut im = vec![0.0; n];
+    fft(&mut re, &mut im);
+    let amplitudes: Vec<f32> = (0..n / 2).map(|i| (re[i] * re[i] + im[i] * im[i]).sqrt() * 4.0 / n as f32).collect();
+
+    let bin_width = sample_rate as f32 / n as f32;
+    let (low, high) = (SPECTRUM_RANGE.0, SPECTRUM_RANGE.1.min(sample_rate as f32 / 2.0));
+    let ratio = (high / low).powf(1.0 / bands as f32);
+    (0..bands)
+        .map(|band| {
+            let from = ((low * ratio.powi(band as i32) / bin_width) as usize).max(1);
+            let to = ((low * ratio.powi(band as i32 + 1) / bin_width) as usize).max(from + 1).min(amplitudes.len());
+            let amplitude = amplitudes.get(from..to).map_or(0.0, |bins| bins.iter().fold(0.0f32, |max, a| max.max(*a)));
+            fraction(amplitude, SPECTRUM_FLOOR_DB)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44_100;
+
+    fn sine(frequency: f32, amplitude: f32) -> Vec<f32> {
+        (0..WINDOW).map(|i| amplitude * (2.0 * PI * frequency * i as f32 / SAMPLE_RATE as f32).sin()).collect()
+    }
+
+    #[test]
+    fn levels_of_a_sine_and_of_silence() {
+        let levels = Levels::measure(&sine(1000.0, 0.5));
+        assert!((levels.rms - 0.5 / 2f32.sqrt()).abs() < 0.01, "{:?}", levels);
+        assert!((levels.peak - 0.5).abs() < 0.01, "{:?}", levels);
+        assert_eq!(Levels::measure(&[0.0; WINDOW]), Levels::default());
+        assert_eq!(Levels::measure(&[]), Levels::default());
+        assert_eq!(Levels::measure(&[2.0, -3.0]), Levels { rms: 1.0, peak: 1.0 });
+    }
+
+    #[test]
+    fn a_sine_peaks_in_its_band() {
+        // 16 bands from 40 Hz share a ratio of about 1.45, so 1 kHz falls in
+        // the band from 798 Hz to 1161 Hz
+        let bands = spectrum(&sine(1000.0, 0.5), SAMPLE_RATE, 16);
+        let loudest = (0..bands.len()).max_by(|&a, &b| bands[a].total_cmp(&bands[b])).unwrap();
+        assert_eq!(loudest, 8, "{:?}", bands);
+        assert!(bands[8] > 0.8, "{:?}", bands);
+        assert!(bands[15] < 0.5, "{:?}", bands);
+    }
+
+    #[test]
+    fn silence_has_no_spectrum() {
+        assert_eq!(spectrum(&[0.0; WINDOW], SAMPLE_RATE, 16), vec![0.0; 16]);
+    }
+
+    #[test]
+    fn short_or_odd_windows_give_empty_bands() {
+        for samples in [&[][..], &[0.5], &[0.5; 3]] {
+            assert_eq!(spectrum(samples, SAMPLE_RATE, 4), vec![0.0; 4]);
+        }
+        assert_eq!(spectrum(&sine(1000.0, 0.5), 0, 4), vec![0.0; 4]);
+    }
+
+    #[test]
+    fn db_fractions_span_the_meter() {
+        assert_eq!(db_fraction(1.0), 1.0);
+        assert_eq!(db_fraction(0.001), 0.0);
+        assert_eq!(db_fraction(0.0), 0.0);
+        assert!((db_fraction(0.1) - 2.0 / 3.0).abs() < 1e-6);
+    }
+}
@@ -1,198 +1,820 @@
 use std::{
     fs,
-    io, path::Path, 
+    io, path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 use ratatui::{
-    crossterm::event::{ self, KeyCode, KeyEvent }, layout::{Constraint, Direction, Layout, Rect}, style::Stylize, text::{Line, Text}, widgets::Widget, DefaultTerminal, Frame
+    crossterm::event::{ self, KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind }, layout::{Constraint, Direction, Layout, Rect}, style::{Style, Stylize}, text::{Line, Span, Text}, DefaultTerminal, Frame
 };
 use rodio::{
     OutputStream, 
     OutputStreamHandle
 };
-use crate::components::{sound_item::SoundItem, sounds_block::SoundsBlock,sound_add_popup::SoundAddPopup};
-use crate::config::Config;
+use crate::command::{self, format_duration, Command};
+use crate::analysis::Levels;
+use crate::components::{command_line::CommandLine, level_meter::{LevelMeter, Spectrum, METER_WIDTH}, notifications::Notifications, confirm_dialog::ConfirmDialog, file_browser::complete_path, help_overlay::HelpOverlay, import_popup::ImportPopup, info_panel::InfoPanel, sounds_block::{SortOrder, SoundsBlock, View},sound_add_popup::SoundAddPopup, sound::probe};
+use crate::config::{Config, ConfigDocument, PresetConfig, SoundConfig, CONFIG_FILE};
+use crate::history::{History, Snapshot};
+use crate::keymap::{format_sequence, Action, Context, KeyChord, Keymap, Lookup, KEYS_FILE};
+use crate::settings::{Settings, SETTINGS_FILE};
+use crate::{import, mix};
+use crate::theme::{self, Theme};
 
 pub const RESOURCES_PATH: &str = "./resources/";
 pub const DEFAULT_VOLUME: f32 = 0.5;
+const INFO_PANEL_WIDTH: u16 = 44;
+/// How often levels are redrawn while sounds play.
+const TICK: Duration = Duration::from_millis(50);
+
+/// Sound files in `sounds.toml` are looked up relative to `RESOURCES_PATH`
+/// first, falling back to the path as written.
+pub fn resolve_sound_path(file: &str) -> String {
+    if file.starts_with("~/") {
+        return expand_home(file);
+    }
+    if Path::new(file).is_absolute() {
+        return file.to_string();
+    }
+    let relative_path = RESOURCES_PATH.to_string() + file;
+    if fs::exists(&relative_path).unwrap_or(false) {
+        relative_path
+    } else {
+        file.to_string()
+    }
+}
+
+/// Text of the master volume in the header.
+fn master_label(mult: f32) -> String {
+    format!("Vol: {:.0}%", mult * 100.0)
+}
+
+/// Replaces a leading `~/` with the user's home directory.
+pub fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home.trim_end_matches('/'), rest),
+        _ => path.to_string(),
+    }
+}
+
+/// Largest count accepted before an action.
+const MAX_COUNT: usize = 999;
+
+/// Actions a count repeats, so `5l` raises the volume five steps.
+fn is_countable(action: Action) -> bool {
+    matches!(
+        action,
+        Action::SelectPrevious | Action::SelectNext | Action::SelectLeft | Action::SelectRight | Action::PageUp | Action::PageDown
+            | Action::VolumeDown | Action::VolumeUp | Action::MasterVolumeUp | Action::MasterVolumeDown
+            | Action::MoveUp | Action::MoveDown
+    )
+}
+
+struct AppLayout {
+    header: Rect,
+    list: Rect,
+    info: Option<Rect>,
+    command: Rect,
+}
+
+/// Actions that wait for the confirmation dialog.
+enum PendingAction {
+    DeleteSound,
+    Quit,
+    SavePreset(String),
+}
 
 pub struct App{
     running: bool,
     sounds_block: SoundsBlock,
     sound_add_popup: SoundAddPopup,
+    import_popup: ImportPopup,
+    confirm_dialog: ConfirmDialog<PendingAction>,
+    command_line: CommandLine,
+    settings: Settings,
     stream_handle: Option<OutputStreamHandle>,
     _stream: Option<OutputStream>,
     general_play_state: bool,
+    presets: Vec<PresetConfig>,
+    current_preset: Option<usize>,
+    notifications: Notifications,
+    info_opened: bool,
+    help: HelpOverlay,
+    keymap: Keymap,
+    /// Keys typed so far of a binding made of several keys, like `g g`.
+    pending_keys: Vec<KeyChord>,
+    /// Number typed before an action to repeat it, like the 5 of `5l`.
+    count: Option<usize>,
+    history: History,
+    /// The sounds of `sounds.toml` as of the save count they were read at,
+    /// so snapshots only read the file again after it was written.
+    library: Option<Option<Vec<SoundConfig>>>,
+    /// Size of the terminal when it was last drawn, to find what the mouse points at.
+    area: Rect,
+    /// State when the mouse button went down, recorded once it goes up.
+    mouse_before: Option<Snapshot>,
+    /// When the sleep timer set with `:timer` pauses every sound.
+    timer: Option<Instant>,
+    /// When sounds were last moved forward without an audio device.
+    last_tick: Instant,
+    /// Last order applied with `o`, so the next press moves on to the next one.
+    sort_order: Option<SortOrder>,
 }
 
 impl App {
     pub fn default() -> Self {
+        let mut notifications = Notifications::new();
         let (stream, stream_handle) = match OutputStream::try_default() {
             Ok((s, h)) => (Some(s), Some(h)),
             Err(e) => {
-                eprintln!("Warning: No audio device available: {}. Audio functionality will be disabled.", e);
+                notifications.warning(format!("No audio device available: {}. Audio functionality will be disabled.", e));
                 (None, None)
             }
         };
-        let sounds_block = SoundsBlock::default();
-        let sound_add_popup = SoundAddPopup::new();
-        App { running: true, sounds_block, stream_handle, _stream: stream, general_play_state: true, sound_add_popup }
+        let mut sounds_block = SoundsBlock::default();
+        let sound_add_popup = SoundAddPopup::new(stream_handle.clone());
+        let import_popup = ImportPopup::new();
+        let settings = Settings::load().unwrap_or_else(|e| {
+            notifications.warning(format!("Could not load {}: {}. Using the default settings.", SETTINGS_FILE, e));
+            Settings::default()
+        });
+        sounds_block.set_view(settings.view);
+        sounds_block.set_meters(settings.meters);
+        let keymap = Keymap::load(settings.keymap).unwrap_or_else(|e| {
+            notifications.error(format!("Error in {}: {}. Using the {} keys.", KEYS_FILE, e, settings.keymap));
+            Keymap::from_preset(settings.keymap)
+        });
+        match Theme::choose(&settings.theme, settings.colors, theme::no_color()) {
+            Ok(chosen) => theme::init(chosen),
+            Err(e) => notifications.error(format!("Error in theme {}: {}. Using the default theme.", settings.theme, e)),
+        }
+        App { running: true, sounds_block, stream_handle, _stream: stream, general_play_state: true, sound_add_popup, import_popup, confirm_dialog: ConfirmDialog::new(), command_line: CommandLine::new(), settings, presets: vec![], current_preset: None, notifications, info_opened: false, help: HelpOverlay::new(), keymap, pending_keys: vec![], count: None, history: History::new(), library: None, sort_order: None, area: Rect::default(), mouse_before: None, timer: None, last_tick: Instant::now() }
     }
 
     pub fn run(&mut self, term: &mut DefaultTerminal) -> io::Result<()> {
-        self.setup_list();
+        self.refresh_list();
         while self.running {
             let size = term.size().unwrap();
-            self.sounds_block.handle_resize(Rect::new(0, 0, size.width.into(), size.height.into()));
+            self.area = Rect::new(0, 0, size.width, size.height);
+            self.sounds_block.handle_resize(self.layout(self.area).list);
+            self.collect_notifications();
             term.draw(|frame: &mut Frame| self.draw(frame))?;
             self.handle_events()?;
+            self.check_timer();
         }
         Ok(())
     }
 
-    fn setup_list(&mut self) {
-        let sounds_file = fs::read_to_string(RESOURCES_PATH.to_string() + "sounds.toml");
-        
-        if sounds_file.is_err() {
-            eprintln!("Warning: sounds.toml file not found. No sounds will be loaded.");
-        } else {
-            let toml_file = sounds_file.unwrap();
-            let config: Config = toml::from_str(&toml_file).unwrap();
-            for (i, sound) in config.sound.iter().enumerate() {
-                let path;
-                let relative_path = &(RESOURCES_PATH.to_string() + &sound.file);
-                if !fs::exists(relative_path).unwrap() {
-                    path = sound.file.to_string();
-                } else {
-                    path = relative_path.to_string();
-                }
-
-                let sound_item = SoundItem::new(
-                    i as u32, 
-                    sound.name.clone(), 
-                    path, 
-                    DEFAULT_VOLUME,
-                    sound.icon.clone(), 
-                    i == 0, 
-                    false, 
-                    self.stream_handle.as_ref()
-                );
-                self.sounds_block.add_sound(sound_item);
+    fn refresh_list(&mut self) {
+        self.library = None;
+        let config = match Config::load() {
+            Ok(config) => config,
+            Err(e) => {
+                self.notifications.warning(format!("Could not load {}: {}. No sounds will be loaded.", CONFIG_FILE, e));
+                return;
             }
-        }
+        };
+        self.presets = config.preset;
+        self.current_preset = self.current_preset.filter(|&i| i < self.presets.len());
+        self.sounds_block.sync_with(&config.sound, self.stream_handle.as_ref());
     }
 
-    fn refresh_list(&mut self) {
-        let sounds_file = fs::read_to_string(RESOURCES_PATH.to_string() + "sounds.toml");
-        
-        if sounds_file.is_err() {
-            eprintln!("Warning: sounds.toml file not found. No sounds will be loaded.");
-        } else {
-            let toml_file = sounds_file.unwrap();
-            let config: Config = toml::from_str(&toml_file).unwrap();
-            for (i, sound) in config.sound.iter().enumerate() {
-                let path;
-                let relative_path = &(RESOURCES_PATH.to_string() + &sound.file);
-                if !fs::exists(relative_path).unwrap() {
-                    path = sound.file.to_string();
-                } else {
-                    path = relative_path.to_string();
-                }
-
-                if self.sounds_block.get_sounds().iter().any(|s| s.get_name() == sound.name && s.get_path() == path) {
-                    continue; // Skip if sound already exists
-                }
+    /// Captures the library and the mixer. The library is read again only
+    /// after something wrote `sounds.toml`.
+    fn snapshot(&mut self) -> Snapshot {
+        if self.sounds_block.take_library_changed() {
+            self.library = None;
+        }
+        let library = self.library
+            .get_or_insert_with(|| Config::load().ok().map(|config| config.sound))
+            .clone();
+        Snapshot {
+            library,
+            mixer: self.sounds_block.mixer_state(),
+            master_volume: self.sounds_block.get_mult(),
+        }
+    }
 
-                let sound_item = SoundItem::new(
-                    i as u32,
-                    sound.name.clone(),
-                    path,
-                    DEFAULT_VOLUME,
-                    sound.icon.clone(),
-                    i == 0,
-                    false,
-                    self.stream_handle.as_ref()
-                );
-                self.sounds_block.add_sound(sound_item);
+    /// Brings the library and the mixer from `current` to `target`. The
+    /// library is only written back when `sounds.toml` still holds the one
+    /// of `current`, so changes made to it outside the app are kept.
+    fn restore(&mut self, current: &Snapshot, target: Snapshot) -> io::Result<()> {
+        if let (Some(library), Some(expected)) = (&target.library, &current.library) {
+            let mut document = ConfigDocument::load()?;
+            let on_disk = document.config()?.sound;
+            if on_disk == *expected {
+                document.set_sounds(library);
+                document.save()?;
+            } else if on_disk != *library {
+                return Err(io::Error::other(format!("{} was changed outside blanket-tui", CONFIG_FILE)));
             }
         }
+        self.refresh_list();
+        self.sounds_block.restore_mixer(&target.mixer, target.master_volume, self.general_play_state);
+        Ok(())
     }
 
-    fn draw(&self, frame: &mut Frame) {
+    /// Splits the screen into the header, the list, the info panel when
+    /// open and the command line.
+    fn layout(&self, area: Rect) -> AppLayout {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Length(3), Constraint::Fill(1)])
-            .split(frame.area());
-        let state_text = Line::from(if self.general_play_state {"Play".bold()} else {"Pause".bold()});
-        let mult_text = Line::from((format!("Vol: {:.0}%", self.sounds_block.get_mult() * 100.0)).bold());
+            .constraints(vec![Constraint::Length(3), Constraint::Fill(1), Constraint::Length(self.command_line.get_opened() as u16)])
+            .split(area);
+        let (list, info) = if self.info_opened {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![Constraint::Fill(1), Constraint::Length(INFO_PANEL_WIDTH)])
+                .split(chunks[1]);
+            (columns[0], Some(columns[1]))
+        } else {
+            (chunks[1], None)
+        };
+        AppLayout { header: chunks[0], list, info, command: chunks[2] }
+    }
+
+    /// Moves the messages left by the components to the notifications.
+    fn collect_notifications(&mut self) {
+        let notifications = self.sounds_block.take_notifications();
+        self.notifications.extend(notifications);
+        let notifications = self.sound_add_popup.take_notifications();
+        self.notifications.extend(notifications);
+    }
 
-        let line = Text::from(vec![state_text, mult_text]);
-        frame.render_widget(line, chunks[0]);
-        frame.render_widget(&self.sounds_block, chunks[1]);
+    fn draw(&self, frame: &mut Frame) {
+        let layout = self.layout(frame.area());
+        let mut state_text = Line::from(if self.general_play_state {"Play".bold()} else {"Pause".bold()});
+        if let Some(deadline) = self.timer {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            state_text.push_span(format!("  Timer: {}", format_duration(remaining + Duration::from_millis(999))));
+        }
+        let unread = self.notifications.get_unread();
+        if unread > 0 {
+            let text = format!("  {} new {}", unread, if unread == 1 { "problem" } else { "problems" });
+            state_text.push_span(Span::styled(text, theme::current().error));
+        }
+        let mult_text = Line::from(master_label(self.sounds_block.get_mult()).bold());
 
+        let mut lines = vec![state_text, mult_text];
+        if !self.pending_keys.is_empty() || self.count.is_some() {
+            let count = self.count.map_or(String::new(), |count| count.to_string());
+            lines.push(Line::from(format!("{}{}", count, format_sequence(&self.pending_keys)).bold()));
+        } else if let Some(toast) = self.notifications.get_toast() {
+            lines.push(toast.to_line());
+        } else if let Some(preset) = self.current_preset.and_then(|i| self.presets.get(i)) {
+            lines.push(Line::from(format!("Preset: {}", preset.name).bold()));
+        }
+        let line = Text::from(lines);
+        let master = self.sounds_block.master_window();
+        let mut header = layout.header;
+        if self.settings.spectrum {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![Constraint::Fill(1), Constraint::Percentage(50)])
+                .split(header);
+            header = columns[0];
+            if let Some((window, sample_rate)) = &master {
+                frame.render_widget(&Spectrum::new(window.clone(), *sample_rate), columns[1]);
+            }
+        }
+        frame.render_widget(line, header);
+        if self.settings.meters && let Some((window, _)) = &master {
+            // Next to the widest master volume label, so it does not move
+            let label_width = master_label(1.0).len() as u16;
+            let row = Rect { y: header.y + 1, height: 1, ..header }.intersection(header);
+            let [_, meter] = Layout::horizontal([Constraint::Length(label_width + 2), Constraint::Max(METER_WIDTH * 2)]).areas(row);
+            frame.render_widget(&LevelMeter::new(Levels::measure(window), Style::default()), meter);
+        }
+        frame.render_widget(&self.sounds_block, layout.list);
+        if let Some(info) = layout.info {
+            frame.render_widget(InfoPanel::new(self.sounds_block.get_selected_sound()), info);
+        }
+
+        if self.command_line.get_opened() {
+            frame.render_widget(&self.command_line, layout.command);
+        }
         if self.sound_add_popup.get_opened() {
             frame.render_widget(&self.sound_add_popup, frame.area());
         }
-        return;
+        if self.import_popup.get_opened() {
+            frame.render_widget(&self.import_popup, frame.area());
+        }
+        if self.confirm_dialog.get_opened() {
+            frame.render_widget(&self.confirm_dialog, frame.area());
+        }
+        if self.help.get_opened() {
+            frame.render_widget(&self.help, frame.area());
+        }
+        if self.notifications.get_log_opened() {
+            frame.render_widget(&self.notifications, frame.area());
+        }
     }
 
     fn handle_events(&mut self) -> io::Result<()> {
+        // Wake up on every tick while levels move, every second while the
+        // timer runs, to count it down, and when the message shown expires
+        let levels_shown = self.settings.meters || self.settings.spectrum;
+        let timeout = if levels_shown && self.sounds_block.is_playing() {
+            Some(TICK)
+        } else {
+            self.timer.map(|_| Duration::from_secs(1))
+        };
+        let timeout = match (timeout, self.notifications.get_toast_remaining()) {
+            (Some(timeout), Some(remaining)) => Some(timeout.min(remaining)),
+            (timeout, remaining) => timeout.or(remaining),
+        };
+        let woken = match timeout {
+            Some(timeout) => !event::poll(timeout)?,
+            None => false,
+        };
+        let now = Instant::now();
+        self.sounds_block.advance(now - self.last_tick);
+        self.last_tick = now;
+        if woken {
+            return Ok(());
+        }
         match event::read()? {
             event::Event::Key(key_event) => self.handle_key_event(key_event),
-            event::Event::Resize(c, r) => {self.sounds_block.handle_resize(Rect::new(0, 0, c, r))}, // Handle resize if needed
+            event::Event::Mouse(mouse_event) => self.handle_mouse_event(mouse_event),
+            event::Event::Resize(c, r) => {
+                self.area = Rect::new(0, 0, c, r);
+                self.sounds_block.handle_resize(self.layout(self.area).list);
+            }
             _ => (), // Ignore other events
         } 
         Ok(())
     }
 
+    /// Which bindings apply to the next key.
+    fn context(&self) -> Context {
+        if self.command_line.get_opened() {
+            Context::Command
+        } else if self.sound_add_popup.get_opened() {
+            Context::Popup
+        } else if self.sounds_block.is_searching() {
+            Context::Search
+        } else if self.sounds_block.get_view() == View::Grid {
+            Context::Grid
+        } else {
+            Context::List
+        }
+    }
+
+    /// Translates the key into an action for the focused context and records
+    /// what it changed, so it can be undone.
     fn handle_key_event(&mut self, key_event: KeyEvent) {
-        match key_event.code {
-            KeyCode::Char('q') => self.handle_exit(key_event),
-            KeyCode::Char('n') => self.handle_popup(key_event),
-            KeyCode::Esc => self.handle_exit(key_event),
-            KeyCode::Enter => {
-                if self.sound_add_popup.get_opened() {
-                    self.sound_add_popup.handle_key_event(key_event);
-                    self.refresh_list();
+        if self.notifications.get_log_opened() {
+            self.notifications.handle_key_event(key_event, self.area);
+            return;
+        }
+        if self.help.get_opened() {
+            self.help.handle_key_event(key_event, self.area);
+            return;
+        }
+        // The dialog, the import popup and the file browser handle raw keys
+        let modal = self.confirm_dialog.get_opened()
+            || self.import_popup.get_opened()
+            || (self.sound_add_popup.get_opened() && self.sound_add_popup.is_browsing());
+        let action = if modal {
+            None
+        } else {
+            self.pending_keys.push(KeyChord::from_event(&key_event));
+            match self.keymap.lookup(self.context(), &self.pending_keys) {
+                Lookup::Action(action) => Some(action),
+                Lookup::Prefix => return,
+                Lookup::None if self.pending_keys.len() > 1 => {
+                    // An unfinished sequence followed by a key that does not continue it
+                    self.pending_keys.clear();
+                    self.count = None;
                     return;
                 }
-                self.general_play_state = !self.general_play_state;
-                self.sounds_block.handle_key_event(key_event.code, self.general_play_state);
+                Lookup::None => None,
             }
-            _ => {
-                if self.sound_add_popup.get_opened() {
-                    self.sound_add_popup.handle_key_event(key_event);
-                    return;
-                }
-                self.sounds_block.handle_key_event(key_event.code, self.general_play_state); 
+        };
+        self.pending_keys.clear();
+        if action.is_none() && self.take_count_digit(&key_event) {
+            return;
+        }
+        let count = self.count.take().unwrap_or(1);
+        match action {
+            Some(Action::Undo) => return self.handle_undo(),
+            Some(Action::Redo) => return self.handle_redo(),
+            _ => {}
+        }
+        let before = self.snapshot();
+        match action {
+            Some(action) if is_countable(action) => (0..count).for_each(|_| self.handle_action(action)),
+            Some(action) => self.handle_action(action),
+            None => self.handle_unbound_key(key_event),
+        }
+        let after = self.snapshot();
+        self.history.record(before, after, action);
+    }
+
+    /// Digits typed in the list build up a count for the next action, unless
+    /// they are bound to an action themselves. A count cannot start with 0.
+    fn take_count_digit(&mut self, key_event: &KeyEvent) -> bool {
+        if !matches!(self.context(), Context::List | Context::Grid) || !key_event.modifiers.is_empty() {
+            return false;
+        }
+        let KeyCode::Char(c) = key_event.code else {
+            return false;
+        };
+        let Some(digit) = c.to_digit(10) else {
+            return false;
+        };
+        if digit == 0 && self.count.is_none() {
+            return false;
+        }
+        self.count = Some(self.count.unwrap_or(0).saturating_mul(10).saturating_add(digit as usize).min(MAX_COUNT));
+        true
+    }
+
+    fn handle_switch_keymap(&mut self) {
+        let preset = self.keymap.get_preset().next();
+        match Keymap::load(preset) {
+            Ok(keymap) => {
+                self.keymap = keymap;
+                self.notifications.info(format!("Using the {} keys", preset));
             }
+            Err(e) => self.notifications.error(format!("Error in {}: {}", KEYS_FILE, e)),
         }
     }
 
-    fn handle_popup(&mut self, key_event: KeyEvent) {
+    /// Mouse events go to the message log, the help or the add / edit popup
+    /// when one is open, otherwise to the list. Other dialogs ignore the
+    /// mouse. A click or a drag is recorded as one change once the button is
+    /// released.
+    fn handle_mouse_event(&mut self, mouse_event: MouseEvent) {
+        if self.notifications.get_log_opened() {
+            self.notifications.handle_mouse_event(mouse_event, self.area);
+            return;
+        }
+        if self.help.get_opened() {
+            self.help.handle_mouse_event(mouse_event, self.area);
+            return;
+        }
         if self.sound_add_popup.get_opened() {
+            self.sound_add_popup.handle_mouse_event(mouse_event, self.area);
+            return;
+        }
+        if self.confirm_dialog.get_opened() || self.import_popup.get_opened() || self.command_line.get_opened() {
+            return;
+        }
+        let list = self.layout(self.area).list;
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.mouse_before = Some(self.snapshot());
+                self.sounds_block.handle_mouse_event(mouse_event, list, self.general_play_state);
+            }
+            MouseEventKind::Up(MouseButton::Left) => {
+                self.sounds_block.handle_mouse_event(mouse_event, list, self.general_play_state);
+                if let Some(before) = self.mouse_before.take() {
+                    let after = self.snapshot();
+                    self.history.record(before, after, None);
+                }
+            }
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let before = self.snapshot();
+                let action = self.sounds_block.handle_mouse_event(mouse_event, list, self.general_play_state);
+                let after = self.snapshot();
+                self.history.record(before, after, action);
+            }
+            _ => {
+                self.sounds_block.handle_mouse_event(mouse_event, list, self.general_play_state);
+            }
+        }
+    }
+
+    /// Keys without an action are text for whatever input has the focus.
+    fn handle_unbound_key(&mut self, key_event: KeyEvent) {
+        if self.confirm_dialog.get_opened() {
+            if let Some(action) = self.confirm_dialog.handle_key_event(key_event) {
+                self.run_action(action);
+            }
+        } else if self.import_popup.get_opened() {
+            self.import_popup.handle_key_event(key_event);
+            if self.import_popup.take_imported() {
+                self.refresh_list();
+            }
+        } else if self.command_line.get_opened() {
+            self.command_line.handle_key_event(key_event);
+        } else if self.sound_add_popup.get_opened() {
             self.sound_add_popup.handle_key_event(key_event);
-        } else {
-            self.sound_add_popup.set_opened(true);
+        } else if self.sounds_block.is_searching() {
+            self.sounds_block.handle_search_input(key_event.code);
         }
     }
 
-    fn handle_exit(&mut self, key_event: KeyEvent) {
-        if key_event.code == KeyCode::Esc {
-            if self.sound_add_popup.get_opened() {
-                self.sound_add_popup.set_opened(false);
-                self.sound_add_popup.clear();
-                return;
+    fn handle_action(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.handle_exit(),
+            Action::Back => {
+                if self.sounds_block.has_search() {
+                    self.sounds_block.clear_search();
+                } else {
+                    self.handle_exit();
+                }
+            }
+            Action::Help => self.help.open(&self.keymap, self.context()),
+            Action::SwitchKeymap => self.handle_switch_keymap(),
+            Action::AddSound => self.sound_add_popup.set_opened(true),
+            Action::EditSound => self.handle_edit_popup(),
+            Action::Import => self.import_popup.set_opened(true),
+            Action::NextPreset => self.handle_next_preset(),
+            Action::SavePreset => self.handle_save_preset(),
+            Action::ExportMix => self.command_line.open_with("export "),
+            Action::Sort => self.handle_sort(),
+            Action::ToggleView => self.handle_toggle_view(),
+            Action::ToggleSpectrum => self.handle_toggle_spectrum(),
+            Action::ToggleInfo => self.info_opened = !self.info_opened,
+            Action::Messages => self.notifications.open_log(),
+            Action::Delete => self.handle_delete(),
+            Action::Undo | Action::Redo => {}
+            Action::PlayPauseAll => {
+                self.general_play_state = !self.general_play_state;
+                self.sounds_block.handle_action(action, self.general_play_state);
             }
-            self.exit();    
+            Action::SubmitPopup => {
+                self.sound_add_popup.handle_action(action);
+                if let Some(edit) = self.sound_add_popup.take_edit() {
+                    self.sounds_block.update_sound(edit, self.stream_handle.as_ref());
+                }
+                self.refresh_list();
+            }
+            Action::ClosePopup | Action::NextInput | Action::BrowseFiles => self.sound_add_popup.handle_action(action),
+            Action::CommandLine => self.command_line.open(),
+            Action::RunCommand => self.run_command(),
+            Action::Complete => self.complete_command(),
+            Action::CloseCommand | Action::HistoryPrevious | Action::HistoryNext => self.command_line.handle_action(action),
+            _ => self.sounds_block.handle_action(action, self.general_play_state),
         }
-        if self.sound_add_popup.get_opened() {
-            self.sound_add_popup.handle_key_event(key_event);
+    }
+
+    fn run_command(&mut self) {
+        let line = self.command_line.submit();
+        if line.is_empty() {
+            return;
+        }
+        match Command::parse(&line).and_then(|command| self.execute_command(command)) {
+            Ok(message) => self.notifications.info(message),
+            Err(e) => self.notifications.error(e),
+        }
+    }
+
+    /// Runs `command`, returning a message for the header.
+    fn execute_command(&mut self, command: Command) -> Result<String, String> {
+        match command {
+            Command::Volume { sound, volume } => {
+                let name = self.sounds_block.set_sound_volume(&sound, volume)?;
+                Ok(format!("{} at {:.0}%", name, volume * 100.0))
+            }
+            Command::Master(volume) => {
+                self.sounds_block.set_master_volume(volume);
+                Ok(format!("Vol: {:.0}%", volume * 100.0))
+            }
+            Command::Solo(name) => {
+                let (name, soloed) = self.sounds_block.toggle_solo(&name)?;
+                Ok(format!("{} {}", if soloed { "Soloed" } else { "Unsoloed" }, name))
+            }
+            Command::LoadPreset(name) => {
+                let index = self.presets
+                    .iter()
+                    .position(|preset| preset.name.eq_ignore_ascii_case(&name))
+                    .ok_or_else(|| format!("no preset called {:?}", name))?;
+                self.load_preset(index);
+                Ok(format!("Loaded {}", self.presets[index].name))
+            }
+            Command::Timer(Some(duration)) => {
+                let deadline = Instant::now().checked_add(duration).ok_or_else(|| format!("{} is too long for a timer", format_duration(duration)))?;
+                self.timer = Some(deadline);
+                Ok(format!("Pausing in {}", format_duration(duration)))
+            }
+            Command::Timer(None) => {
+                self.timer = None;
+                Ok("Timer cancelled".to_string())
+            }
+            Command::Add(file) => self.add_sound_file(&file),
+            Command::Export { file, with_files } => self.export_mix(file, with_files),
+        }
+    }
+
+    /// Adds `file` to the library, named after the file like imported sounds.
+    fn add_sound_file(&mut self, file: &str) -> Result<String, String> {
+        let path = resolve_sound_path(file);
+        probe(&path)?;
+        let name = import::name_from_path(Path::new(&path));
+        let sound = SoundConfig {
+            name: name.clone(),
+            file: file.to_string(),
+            icon: import::icon_for_name(&name).to_string(),
+            ..SoundConfig::default()
+        };
+        import::append_sounds(&[sound]).map_err(|e| e.to_string())?;
+        self.refresh_list();
+        Ok(format!("Added {}", name))
+    }
+
+    fn complete_command(&mut self) {
+        let sounds: Vec<String> = self.sounds_block.get_sounds().iter().map(|sound| sound.get_name().to_string()).collect();
+        let groups: Vec<String> = self.sounds_block.groups().into_iter().map(|(group, _)| group).collect();
+        let presets: Vec<String> = self.presets.iter().map(|preset| preset.name.clone()).collect();
+        self.command_line.complete(|line| {
+            let file_prefix = ["add ", "export --with-files ", "export "].into_iter().find(|prefix| line.starts_with(prefix));
+            match file_prefix {
+                Some(prefix) => complete_path(&line[prefix.len()..]).map(|path| vec![format!("{}{}", prefix, path)]).unwrap_or_default(),
+                None => command::complete(line, &sounds, &groups, &presets),
+            }
+        });
+    }
+
+    /// Pauses every sound once the sleep timer runs out.
+    fn check_timer(&mut self) {
+        if self.timer.is_some_and(|deadline| deadline <= Instant::now()) {
+            self.timer = None;
+            if self.general_play_state {
+                self.handle_action(Action::PlayPauseAll);
+            }
+            self.notifications.info("Timer ended, sounds paused");
+        }
+    }
+
+    /// A change that cannot be undone stays in the undo history.
+    fn handle_undo(&mut self) {
+        let Some((current, target)) = self.history.undo() else {
+            self.notifications.info("Nothing to undo");
+            return;
+        };
+        match self.restore(&current, target) {
+            Ok(()) => self.notifications.info("Undone"),
+            Err(e) => {
+                self.history.redo();
+                self.notifications.error(format!("Could not undo: {}", e));
+            }
+        }
+    }
+
+    fn handle_redo(&mut self) {
+        let Some((current, target)) = self.history.redo() else {
+            self.notifications.info("Nothing to redo");
+            return;
+        };
+        match self.restore(&current, target) {
+            Ok(()) => self.notifications.info("Redone"),
+            Err(e) => {
+                self.history.undo();
+                self.notifications.error(format!("Could not redo: {}", e));
+            }
+        }
+    }
+
+    fn handle_edit_popup(&mut self) {
+        if let Some(sound) = self.sounds_block.get_selected_sound() {
+            self.sound_add_popup.open_edit(sound.get_name(), sound.get_path());
+        }
+    }
+
+    /// Switches between the list and the grid, remembering the choice in
+    /// `settings.toml`.
+    fn handle_toggle_view(&mut self) {
+        let view = self.sounds_block.get_view().next();
+        self.sounds_block.set_view(view);
+        match self.settings.set_view(view) {
+            Ok(()) => self.notifications.info(format!("Showing the {}", view)),
+            Err(e) => self.notifications.warning(format!("Showing the {}, but could not save it to {}: {}", view, SETTINGS_FILE, e)),
+        }
+    }
+
+    fn handle_toggle_spectrum(&mut self) {
+        let spectrum = !self.settings.spectrum;
+        let shown = if spectrum { "Showing" } else { "Hiding" };
+        match self.settings.set_spectrum(spectrum) {
+            Ok(()) => self.notifications.info(format!("{} the spectrum", shown)),
+            Err(e) => self.notifications.warning(format!("{} the spectrum, but could not save it to {}: {}", shown, SETTINGS_FILE, e)),
+        }
+    }
+
+    /// Sorts the list by the next order in the cycle and saves it.
+    fn handle_sort(&mut self) {
+        let order = self.sort_order.map_or(SortOrder::Name, SortOrder::next);
+        self.sort_order = Some(order);
+        self.sounds_block.sort_sounds(order);
+        self.notifications.info(format!("Sorted by {}", order));
+    }
+
+    fn handle_next_preset(&mut self) {
+        if self.presets.is_empty() {
+            return;
+        }
+        let next = self.current_preset.map_or(0, |i| (i + 1) % self.presets.len());
+        self.load_preset(next);
+    }
+
+    fn load_preset(&mut self, index: usize) {
+        self.sounds_block.apply_preset(&self.presets[index], self.general_play_state);
+        self.current_preset = Some(index);
+    }
+
+    /// The mix playing, named after the loaded preset.
+    fn current_mix(&self) -> PresetConfig {
+        let name = self.current_preset
+            .and_then(|i| self.presets.get(i))
+            .map_or_else(|| "Mix".to_string(), |preset| preset.name.clone());
+        self.sounds_block.to_preset(name)
+    }
+
+    /// Writes the current mix as a shareable document, to `mixes_dir()`
+    /// unless `file` is given. Files ending in `.tar` are bundles.
+    fn export_mix(&mut self, file: Option<String>, with_files: bool) -> Result<String, String> {
+        let preset = self.current_mix();
+        let path = match file {
+            Some(file) => PathBuf::from(expand_home(&file)),
+            None => mix::mixes_dir().join(format!("{}.toml", mix::slug(&preset.name))),
+        };
+        let result = Config::load().and_then(|config| {
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                fs::create_dir_all(dir)?;
+            }
+            mix::export(&preset, &config.sound, &path, with_files)
+        });
+        match result {
+            Ok(()) => Ok(format!("Exported mix to {}", path.display())),
+            Err(e) => Err(format!("Could not export the mix: {}", e)),
+        }
+    }
+
+    fn handle_exit(&mut self) {
+        let playing = self.sounds_block.get_sounds().iter().filter(|sound| sound.is_active()).count();
+        if self.general_play_state && playing > 0 {
+            self.confirm("Quit", format!("{} sound(s) are playing. Quit anyway?", playing), PendingAction::Quit);
         } else {
             self.exit();
         }
     }
 
+    fn handle_delete(&mut self) {
+        if let Some(sound) = self.sounds_block.get_selected_sound() {
+            let message = format!("Delete {} from {}?", sound.get_name(), CONFIG_FILE);
+            self.confirm("Delete Sound", message, PendingAction::DeleteSound);
+        }
+    }
+
+    /// Saves the current mix over the loaded preset, or as a new preset when
+    /// none is loaded.
+    fn handle_save_preset(&mut self) {
+        match self.current_preset.and_then(|i| self.presets.get(i)) {
+            Some(preset) => {
+                let name = preset.name.clone();
+                let message = format!("Overwrite preset {} with the current mix?", name);
+                self.confirm("Overwrite Preset", message, PendingAction::SavePreset(name));
+            }
+            None => {
+                let name = (1..)
+                    .map(|n| format!("Preset {}", n))
+                    .find(|name| !self.presets.iter().any(|preset| &preset.name == name))
+                    .unwrap_or_default();
+                self.run_action(PendingAction::SavePreset(name));
+            }
+        }
+    }
+
+    /// Opens the confirmation dialog for `action`, or runs it right away when
+    /// confirmations are turned off in the settings.
+    fn confirm(&mut self, title: &str, message: String, action: PendingAction) {
+        if self.settings.confirm {
+            self.confirm_dialog.open(title, message, action);
+        } else {
+            self.run_action(action);
+        }
+    }
+
+    fn run_action(&mut self, action: PendingAction) {
+        match action {
+            PendingAction::DeleteSound => self.sounds_block.delete_selected_sound_from_list(),
+            PendingAction::Quit => self.exit(),
+            PendingAction::SavePreset(name) => self.save_preset(name),
+        }
+    }
+
+    fn save_preset(&mut self, name: String) {
+        let preset = self.sounds_block.to_preset(name);
+        let result = ConfigDocument::load().and_then(|mut document| {
+            document.set_preset(&preset);
+            document.save()
+        });
+        if let Err(e) = result {
+            self.notifications.error(format!("Could not save the preset: {}", e));
+            return;
+        }
+        self.refresh_list();
+        self.current_preset = self.presets.iter().position(|p| p.name == preset.name);
+        self.notifications.info(format!("Saved preset {}", preset.name));
+    }
+
+    /// Stops the app, keeping the mix for `blanket-tui export-mix`. Failing
+    /// to keep it only matters to a later export, so it does not stop the
+    /// app from quitting.
     fn exit(&mut self) {
+        let _ = mix::save_current(&self.current_mix());
         self.running = false;
     }
 }
\ No newline at end of file
@@ -0,0 +1,439 @@
+//! Importer for the settings of GNOME Blanket.
+//!
+//! Blanket keeps its state in GSettings, which can be read either from the
+//! keyfile backend (`~/.config/glib-2.0/settings/keyfile`, also used by the
+//! Flatpak) or from a `dconf dump /com/rafaelmardojai/Blanket/` export. Both are
+//! ini-style files whose values are GVariant text.
+
+use std::{collections::BTreeMap, fs, io, path::{Path, PathBuf}};
+use crate::{
+    app::resolve_sound_path,
+    config::{ConfigDocument, PresetConfig, SoundConfig},
+    import::{icon_for_name, name_from_path},
+};
+
+/// Keyfiles searched when no file is given, Flatpak install first.
+pub const DEFAULT_KEYFILES: &[&str] = &[
+    ".var/app/com.rafaelmardojai.Blanket/config/glib-2.0/settings/keyfile",
+    ".config/glib-2.0/settings/keyfile",
+];
+
+/// What an import would change, and what it could not map.
+pub struct BlanketImport {
+    pub sounds: Vec<SoundConfig>,
+    pub presets: Vec<PresetConfig>,
+    pub mapped: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+impl BlanketImport {
+    /// Writes the new sounds and presets to `sounds.toml` in a single save.
+    pub fn apply(&self) -> io::Result<()> {
+        let mut document = ConfigDocument::load()?;
+        for sound in &self.sounds {
+            document.push_sound(sound);
+        }
+        for preset in &self.presets {
+            document.set_preset(preset);
+        }
+        document.save()
+    }
+}
+
+/// Finds Blanket's settings keyfile in the home directory.
+pub fn find_keyfile() -> Option<PathBuf> {
+    let home = std::env::var("HOME").ok()?;
+    DEFAULT_KEYFILES
+        .iter()
+        .map(|file| Path::new(&home).join(file))
+        .find(|path| fs::read_to_string(path).is_ok_and(|contents| contents.contains("rafaelmardojai/Blanket")))
+}
+
+/// Reads a Blanket settings export and maps it onto the current library.
+pub fn read_settings(path: &Path) -> io::Result<BlanketImport> {
+    let contents = fs::read_to_string(path)?;
+    let config = ConfigDocument::load()?.config()?;
+    import_settings(&contents, &config.sound)
+}
+
+/// Maps the settings in `contents` onto `library`. Values that are not valid
+/// GVariant text make the whole import fail rather than be skipped.
+fn import_settings(contents: &str, library: &[SoundConfig]) -> io::Result<BlanketImport> {
+    let sections = parse_keyfile(contents)?;
+
+    let mut import = BlanketImport { sounds: vec![], presets: vec![], mapped: vec![], skipped: vec![] };
+    let root = sections.iter().find(|(_, keys)| keys.contains_key("presets") || keys.contains_key("active-preset"));
+    let (root_name, root_keys) = root.cloned().unwrap_or_default();
+    let master_volume = setting(&root_keys, &root_name, "volume")?
+        .and_then(|v| v.as_f64())
+        .unwrap_or(1.0) as f32;
+
+    // Library names by lowercase name and by file stem, which is how Blanket
+    // names its bundled sounds ("coffee-shop", "summer-night", ...)
+    let mut known: BTreeMap<String, String> = BTreeMap::new();
+    for sound in library {
+        known.insert(normalize(&sound.name), sound.name.clone());
+        if let Some(stem) = Path::new(&sound.file).file_stem() {
+            known.entry(normalize(&stem.to_string_lossy())).or_insert_with(|| sound.name.clone());
+        }
+    }
+
+    let custom_audios = setting(&root_keys, &root_name, "custom-audios")?;
+    for (name, file) in custom_audio_entries(custom_audios) {
+        let file = file.strip_prefix("file://").unwrap_or(&file).to_string();
+        if let Some(existing) = library.iter().find(|s| resolve_sound_path(&s.file) == file) {
+            import.mapped.push(format!("custom sound {} -> {} (already in library)", name, existing.name));
+            known.insert(normalize(&name), existing.name.clone());
+            continue;
+        }
+        if !Path::new(&file).exists() {
+            import.skipped.push(format!("custom sound {}: {} does not exist", name, file));
+            continue;
+        }
+        import.mapped.push(format!("custom sound {} -> new sound from {}", name, file));
+        known.insert(normalize(&name), name.clone());
+        import.sounds.push(SoundConfig { icon: icon_for_name(&name).to_string(), name, file, ..Default::default() });
+    }
+
+    for (section, keys) in &sections {
+        if !keys.contains_key("visible-name") && !keys.contains_key("sounds-volume") {
+            continue;
+        }
+        let id = section.trim_end_matches('/').rsplit('/').next().unwrap_or(section).to_string();
+        let name = setting(keys, section, "visible-name")?
+            .and_then(|v| v.as_str().map(str::to_string))
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| id.clone());
+        let mut preset = PresetConfig { name: name.clone(), master_volume, sounds: BTreeMap::new() };
+        let volumes = setting(keys, section, "sounds-volume")?;
+        for (sound, volume) in dict_entries(volumes) {
+            let Some(volume) = volume.as_f64() else { continue };
+            // Blanket plays every sound whose slider is above zero
+            if volume <= 0.0 {
+                continue;
+            }
+            match known.get(&normalize(&sound)) {
+                Some(library_name) => {
+                    preset.sounds.insert(library_name.clone(), volume.clamp(0.0, 1.0) as f32);
+                }
+                None => import.skipped.push(format!("preset {}: no sound matches {}", name, sound)),
+            }
+        }
+        import.mapped.push(format!("preset {} -> {} active sound(s)", name, preset.sounds.len()));
+        import.presets.push(preset);
+    }
+    Ok(import)
+}
+
+/// Parses `key` of `section`, or returns `None` when it is not set.
+fn setting(keys: &BTreeMap<String, String>, section: &str, key: &str) -> io::Result<Option<GVariant>> {
+    keys.get(key)
+        .map(|text| parse_gvariant(text).ok_or_else(|| invalid_data(format!("[{}] {} is not a valid value: {}", section, key, text))))
+        .transpose()
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn normalize(name: &str) -> String {
+    name.to_lowercase().replace(['-', '_'], " ")
+}
+
+/// `custom-audios` was a name to path dictionary in older releases and a plain
+/// list of paths in newer ones.
+fn custom_audio_entries(value: Option<GVariant>) -> Vec<(String, String)> {
+    match value {
+        Some(GVariant::Dict(entries)) => entries
+            .into_iter()
+            .filter_map(|(k, v)| Some((k.as_str()?.to_string(), v.as_str()?.to_string())))
+            .collect(),
+        Some(GVariant::Array(items)) => items
+            .into_iter()
+            .filter_map(|item| {
+                let file = item.as_str()?.to_string();
+                let path = file.strip_prefix("file://").unwrap_or(&file).to_string();
+                Some((name_from_path(Path::new(&path)), file))
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn dict_entries(value: Option<GVariant>) -> Vec<(String, GVariant)> {
+    match value {
+        Some(GVariant::Dict(entries)) => entries
+            .into_iter()
+            .filter_map(|(k, v)| Some((k.as_str()?.to_string(), v)))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Splits an ini-style file into its sections and raw `key=value` pairs.
+fn parse_keyfile(contents: &str) -> io::Result<Vec<(String, BTreeMap<String, String>)>> {
+    let mut sections: Vec<(String, BTreeMap<String, String>)> = vec![];
+    for (n, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((name.to_string(), BTreeMap::new()));
+        } else if let (Some((key, value)), Some((_, keys))) = (line.split_once('='), sections.last_mut()) {
+            keys.insert(key.trim().to_string(), value.trim().to_string());
+        } else {
+            return Err(invalid_data(format!("line {} is neither a [section] nor a key=value pair: {}", n + 1, line)));
+        }
+    }
+    // Keyfiles hold the settings of every application, so keep Blanket's only
+    if sections.iter().any(|(name, _)| name.contains("rafaelmardojai/Blanket")) {
+        sections.retain(|(name, _)| name.contains("rafaelmardojai/Blanket"));
+    }
+    Ok(sections)
+}
+
+/// The subset of GVariant text format that Blanket's settings use.
+#[derive(Clone, Debug)]
+enum GVariant {
+    Str(String),
+    Number(f64),
+    /// Booleans and other values the importer has no use for.
+    Other,
+    Array(Vec<GVariant>),
+    Dict(Vec<(GVariant, GVariant)>),
+}
+
+impl GVariant {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            GVariant::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            GVariant::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+/// Deepest nesting of containers, variants and type prefixes accepted, so
+/// hostile input cannot exhaust the stack.
+const MAX_DEPTH: usize = 32;
+
+/// Parses a whole value, returning `None` for anything malformed, including
+/// text left after the value.
+fn parse_gvariant(text: &str) -> Option<GVariant> {
+    let mut parser = GVariantParser { chars: text.chars().collect(), pos: 0, depth: 0 };
+    let value = parser.value()?;
+    parser.peek().is_none().then_some(value)
+}
+
+struct GVariantParser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+}
+
+impl GVariantParser {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn word(&mut self) -> String {
+        self.peek();
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| c.is_alphanumeric() || matches!(c, '.' | '-' | '+' | '_')) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn value(&mut self) -> Option<GVariant> {
+        if self.depth == MAX_DEPTH {
+            return None;
+        }
+        self.depth += 1;
+        let value = self.nested_value();
+        self.depth -= 1;
+        value
+    }
+
+    fn nested_value(&mut self) -> Option<GVariant> {
+        match self.peek()? {
+            '\'' | '"' => self.string(),
+            '[' => self.array(),
+            '{' => self.dict(),
+            '<' => {
+                self.pos += 1;
+                let inner = self.value();
+                self.eat('>').then_some(inner?)
+            }
+            '@' => {
+                // Type annotation such as `@as []` or `@a{sd} {}`
+                while self.chars.get(self.pos).is_some_and(|c| !c.is_whitespace()) {
+                    self.pos += 1;
+                }
+                self.value()
+            }
+            _ => {
+                let word = self.word();
+                match word.as_str() {
+                    "true" | "false" | "nothing" => Some(GVariant::Other),
+                    "" => None,
+                    // Type prefixes such as `uint32 5` or `double 0.5`
+                    w if w.chars().next().is_some_and(char::is_alphabetic) => self.value(),
+                    w => w.parse().ok().map(GVariant::Number),
+                }
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<GVariant> {
+        let quote = self.chars[self.pos];
+        self.pos += 1;
+        let mut text = String::new();
+        while let Some(&c) = self.chars.get(self.pos) {
+            self.pos += 1;
+            match c {
+                '\\' => {
+                    let escaped = *self.chars.get(self.pos)?;
+                    self.pos += 1;
+                    text.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        other => other,
+                    });
+                }
+                c if c == quote => return Some(GVariant::Str(text)),
+                c => text.push(c),
+            }
+        }
+        None
+    }
+
+    fn array(&mut self) -> Option<GVariant> {
+        self.pos += 1;
+        let mut items = vec![];
+        while !self.eat(']') {
+            items.push(self.value()?);
+            if !self.eat(',') && self.peek() != Some(']') {
+                return None;
+            }
+        }
+        Some(GVariant::Array(items))
+    }
+
+    fn dict(&mut self) -> Option<GVariant> {
+        self.pos += 1;
+        let mut entries = vec![];
+        while !self.eat('}') {
+            let key = self.value()?;
+            if !self.eat(':') {
+                return None;
+            }
+            entries.push((key, self.value()?));
+            if !self.eat(',') && self.peek() != Some('}') {
+                return None;
+            }
+        }
+        Some(GVariant::Dict(entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYFILE: &str = include_str!("../tests/fixtures/blanket_keyfile.ini");
+    const DCONF: &str = include_str!("../tests/fixtures/blanket_dconf.ini");
+
+    fn library() -> Vec<SoundConfig> {
+        [("Rain", "./sounds/rain.ogg"), ("Coffee Shop", "./sounds/coffee-shop.ogg"), ("Wind", "./sounds/wind.ogg")]
+            .into_iter()
+            .map(|(name, file)| SoundConfig { name: name.to_string(), file: file.to_string(), ..SoundConfig::default() })
+            .collect()
+    }
+
+    fn volumes(preset: &PresetConfig) -> Vec<(&str, f32)> {
+        preset.sounds.iter().map(|(name, volume)| (name.as_str(), *volume)).collect()
+    }
+
+    #[test]
+    fn keyfiles_are_mapped_onto_the_library() {
+        let import = import_settings(KEYFILE, &library()).unwrap();
+        assert!(import.sounds.is_empty());
+        let names: Vec<&str> = import.presets.iter().map(|preset| preset.name.as_str()).collect();
+        assert_eq!(names, ["Rainy Café", "d4e5f6"]);
+        assert_eq!(import.presets[0].master_volume, 0.75);
+        assert_eq!(volumes(&import.presets[0]), [("Coffee Shop", 0.25), ("Rain", 0.5)]);
+        assert!(import.presets[1].sounds.is_empty());
+        assert_eq!(import.skipped, [
+            "custom sound Old Radio: /nonexistent/Rock 'n' Roll\\Radio.ogg does not exist",
+            "preset Rainy Café: no sound matches thunder",
+        ]);
+    }
+
+    #[test]
+    fn dconf_dumps_are_mapped_onto_the_library() {
+        let import = import_settings(DCONF, &library()).unwrap();
+        assert_eq!(import.presets.len(), 1);
+        assert_eq!(import.presets[0].name, "a1b2c3");
+        assert_eq!(import.presets[0].master_volume, 1.0);
+        assert_eq!(volumes(&import.presets[0]), [("Rain", 0.8)]);
+        assert_eq!(import.skipped, [
+            "custom sound Night Train: /nonexistent/sounds/night_train.ogg does not exist",
+            "preset a1b2c3: no sound matches summer-night",
+        ]);
+    }
+
+    #[test]
+    fn gvariant_text_is_parsed() {
+        let text = |text: &str| parse_gvariant(text).and_then(|value| value.as_str().map(str::to_string));
+        let number = |text: &str| parse_gvariant(text).and_then(|value| value.as_f64());
+        assert_eq!(text(r"'it\'s'"), Some("it's".to_string()));
+        assert_eq!(text(r#""C:\\sounds\\rain.ogg""#), Some(r"C:\sounds\rain.ogg".to_string()));
+        assert_eq!(number("<double 0.5>"), Some(0.5));
+        assert_eq!(number("uint32 5"), Some(5.0));
+        assert_eq!(number("1e-3"), Some(0.001));
+        assert!(matches!(parse_gvariant("@a{sd} {}"), Some(GVariant::Dict(entries)) if entries.is_empty()));
+        assert!(matches!(parse_gvariant("@as []"), Some(GVariant::Array(items)) if items.is_empty()));
+        assert!(matches!(parse_gvariant("{'a': <1.0>, 'b': 2}"), Some(GVariant::Dict(entries)) if entries.len() == 2));
+    }
+
+    #[test]
+    fn malformed_values_are_rejected() {
+        let nested = "[".repeat(10_000);
+        for text in ["", "'unterminated", r"'\", "[1, 2", "[1 2]", "{'a' 1}", "{'a': }", "<0.5", "@as", "0.5 0.6", "double", "<<<>>>", &nested] {
+            assert!(parse_gvariant(text).is_none(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn malformed_files_are_errors() {
+        let bad_value = "[com/rafaelmardojai/Blanket]\npresets=@as []\ncustom-audios=['/a.ogg'\n";
+        let error = import_settings(bad_value, &library()).err().unwrap();
+        assert_eq!(error.to_string(), "[com/rafaelmardojai/Blanket] custom-audios is not a valid value: ['/a.ogg'");
+        let bad_volume = "[/]\nvolume=loud\npresets=@as []\n";
+        assert!(import_settings(bad_volume, &library()).is_err());
+        let bad_preset = "[presets/a]\nsounds-volume={'rain': 0.5\n";
+        assert!(import_settings(bad_preset, &library()).is_err());
+        let bad_line = "[/]\npresets=@as []\nnot a setting\n";
+        assert_eq!(import_settings(bad_line, &library()).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
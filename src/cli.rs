@@ -0,0 +1,148 @@
+use std::{io, path::{Path, PathBuf}};
+use crate::{blanket, config::Config, import::{append_sounds, scan_directory}, mix};
+
+const USAGE: &str = "\
+Usage: blanket-tui [COMMAND]
+
+Without a command the TUI is started.
+
+Commands:
+  import <DIR> [--dry-run]   Add every audio file under DIR to sounds.toml
+  import-blanket [FILE] [--dry-run]
+                             Import presets and custom sounds from GNOME Blanket.
+                             FILE is a GSettings keyfile or the output of
+                             `dconf dump /com/rafaelmardojai/Blanket/`
+  export-mix <OUT> [--preset NAME] [--with-files]
+                             Write the mix playing when the TUI last quit, or a
+                             preset, as a shareable mix document. When OUT ends
+                             in .tar the audio files are bundled with it
+  import-mix <FILE> [--name NAME] [--dry-run]
+                             Save a mix document or bundle as a preset
+  help                       Show this message";
+
+/// Runs a command given on the command line instead of the TUI.
+pub fn run(args: &[String]) -> io::Result<()> {
+    match args[0].as_str() {
+        "import" => import(&args[1..]),
+        "import-blanket" => import_blanket(&args[1..]),
+        "export-mix" => export_mix(&args[1..]),
+        "import-mix" => import_mix(&args[1..]),
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => Err(usage_error(&format!("unknown command: {}", other))),
+    }
+}
+
+fn usage_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("{}\n\n{}", message, USAGE))
+}
+
+fn import(args: &[String]) -> io::Result<()> {
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let Some(dir) = args.iter().find(|arg| !arg.starts_with("--")) else {
+        return Err(usage_error("import needs a directory"));
+    };
+    let result = scan_directory(Path::new(dir))?;
+    for sound in &result.sounds {
+        println!("{} {}  <- {}", sound.icon, sound.name, sound.file);
+    }
+    if result.already_imported > 0 {
+        println!("Skipped {} file(s) already in the library", result.already_imported);
+    }
+    if result.sounds.is_empty() {
+        println!("Nothing to import");
+        return Ok(());
+    }
+    if dry_run {
+        println!("Would import {} sound(s)", result.sounds.len());
+        return Ok(());
+    }
+    append_sounds(&result.sounds)?;
+    println!("Imported {} sound(s)", result.sounds.len());
+    Ok(())
+}
+
+fn import_blanket(args: &[String]) -> io::Result<()> {
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let file = match args.iter().find(|arg| !arg.starts_with("--")) {
+        Some(file) => PathBuf::from(file),
+        None => blanket::find_keyfile().ok_or_else(|| {
+            usage_error("no Blanket keyfile found, export one with `dconf dump /com/rafaelmardojai/Blanket/ > blanket.ini`")
+        })?,
+    };
+    let import = blanket::read_settings(&file)?;
+    for line in &import.mapped {
+        println!("mapped:  {}", line);
+    }
+    for line in &import.skipped {
+        println!("skipped: {}", line);
+    }
+    if import.sounds.is_empty() && import.presets.is_empty() {
+        println!("Nothing to import");
+        return Ok(());
+    }
+    if dry_run {
+        println!("Would import {} sound(s) and {} preset(s)", import.sounds.len(), import.presets.len());
+        return Ok(());
+    }
+    import.apply()?;
+    println!("Imported {} sound(s) and {} preset(s)", import.sounds.len(), import.presets.len());
+    Ok(())
+}
+
+fn export_mix(args: &[String]) -> io::Result<()> {
+    let with_files = args.iter().any(|arg| arg == "--with-files");
+    let preset_name = args.iter().position(|arg| arg == "--preset").and_then(|i| args.get(i + 1));
+    let Some(out) = args.iter().enumerate().find(|(i, arg)| {
+        !arg.starts_with("--") && (*i == 0 || args[i - 1] != "--preset")
+    }).map(|(_, arg)| arg) else {
+        return Err(usage_error("export-mix needs an output file"));
+    };
+    let config = Config::load()?;
+    let preset = match preset_name {
+        Some(preset_name) => config
+            .preset
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(preset_name))
+            .cloned()
+            .ok_or_else(|| usage_error(&format!("no preset named {}", preset_name)))?,
+        None => mix::load_current()?,
+    };
+    mix::export(&preset, &config.sound, Path::new(out), with_files)?;
+    println!("Exported {} to {}", preset.name, out);
+    Ok(())
+}
+
+fn import_mix(args: &[String]) -> io::Result<()> {
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let name = args.iter().position(|arg| arg == "--name").and_then(|i| args.get(i + 1));
+    let Some(file) = args.iter().enumerate().find(|(i, arg)| {
+        !arg.starts_with("--") && (*i == 0 || args[i - 1] != "--name")
+    }).map(|(_, arg)| arg) else {
+        return Err(usage_error("import-mix needs a mix file"));
+    };
+    let config = Config::load()?;
+    let (mix, base_dir) = mix::read_mix(Path::new(file))?;
+    let mut import = mix.to_preset(&config.sound, &base_dir)?;
+    if let Some(name) = name {
+        import.preset.name = name.clone();
+    }
+    for (sound, volume) in &import.preset.sounds {
+        println!("{} at {:.0}%", sound, volume * 100.0);
+    }
+    for sound in &import.new_sounds {
+        println!("new sound: {} <- {}", sound.name, sound.file);
+    }
+    for sound in &import.missing {
+        println!("missing: {}", sound);
+    }
+    if dry_run {
+        println!("Would save preset {}", import.preset.name);
+        return Ok(());
+    }
+    mix::apply_import(&import)?;
+    println!("Saved preset {}", import.preset.name);
+    Ok(())
+}
@@ -0,0 +1,219 @@
+//! Commands typed after `:`, such as `vol rain 40` or `timer 30m`.
+
+use std::time::Duration;
+
+/// Command names, in the order completion offers them.
+pub const COMMANDS: &[&str] = &["vol", "master", "solo", "preset", "timer", "add", "export"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// Volume of a sound, from 0 to 1.
+    Volume { sound: String, volume: f32 },
+    Master(f32),
+    /// Solo a sound or a category, or unsolo it.
+    Solo(String),
+    LoadPreset(String),
+    /// Pause every sound once the duration has passed, or cancel the timer.
+    Timer(Option<Duration>),
+    Add(String),
+    /// Export the current mix, to the mixes directory without a file.
+    Export { file: Option<String>, with_files: bool },
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim().trim_start_matches(':').trim_start();
+        let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = args.trim();
+        match name {
+            "vol" | "volume" => {
+                let (sound, volume) = args.rsplit_once(char::is_whitespace).ok_or("usage: vol <sound> <0-100>")?;
+                let sound = sound.trim();
+                if sound.is_empty() {
+                    return Err("usage: vol <sound> <0-100>".to_string());
+                }
+                Ok(Command::Volume { sound: sound.to_string(), volume: parse_percent(volume)? })
+            }
+            "master" => Ok(Command::Master(parse_percent(args)?)),
+            "solo" if !args.is_empty() => Ok(Command::Solo(args.to_string())),
+            "solo" => Err("usage: solo <sound or category>".to_string()),
+            "preset" => match args.split_once(char::is_whitespace) {
+                Some(("load", name)) if !name.trim().is_empty() => Ok(Command::LoadPreset(name.trim().to_string())),
+                _ => Err("usage: preset load <name>".to_string()),
+            },
+            "timer" => match args {
+                "off" | "0" => Ok(Command::Timer(None)),
+                "" => Err("usage: timer <duration>, like 30m or 1h30m, or timer off".to_string()),
+                _ => Ok(Command::Timer(Some(parse_duration(args)?))),
+            },
+            "add" if !args.is_empty() => Ok(Command::Add(args.to_string())),
+            "add" => Err("usage: add <file>".to_string()),
+            "export" => {
+                let (with_files, file) = match args.strip_prefix("--with-files") {
+                    Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => (true, rest.trim()),
+                    _ => (false, args),
+                };
+                Ok(Command::Export { file: (!file.is_empty()).then(|| file.to_string()), with_files })
+            }
+            "" => Err("empty command".to_string()),
+            _ => Err(format!("unknown command {:?}", name)),
+        }
+    }
+}
+
+/// `40` and `40%` are both 0.4.
+fn parse_percent(text: &str) -> Result<f32, String> {
+    let number = text.trim().trim_end_matches('%');
+    match number.parse::<f32>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent / 100.0),
+        _ => Err(format!("{:?} is not a volume from 0 to 100", text)),
+    }
+}
+
+/// Parses durations such as `30m`, `1h30m`, `90s` or `45`, which counts
+/// minutes.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("{:?} is not a duration like 30m or 1h30m", text);
+    if let Ok(minutes) = text.parse::<u64>() {
+        return minutes.checked_mul(60).map(Duration::from_secs).ok_or_else(invalid);
+    }
+    let mut seconds: u64 = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let value: u64 = number.parse().map_err(|_| invalid())?;
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        seconds = value.checked_mul(unit).and_then(|value| seconds.checked_add(value)).ok_or_else(invalid)?;
+        number.clear();
+    }
+    if !number.is_empty() || seconds == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// `1:05:00` for an hour and five minutes, `4:30` below an hour.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// Lines `line` could be completed to: command names for the first word,
+/// then sound names for `vol`, sound and category names for `solo` and
+/// preset names for `preset load`. Files for `add` are completed by the
+/// caller.
+pub fn complete(line: &str, sounds: &[String], groups: &[String], presets: &[String]) -> Vec<String> {
+    let Some((name, args)) = line.split_once(' ') else {
+        return COMMANDS.iter().filter(|command| command.starts_with(line)).map(|command| format!("{} ", command)).collect();
+    };
+    let solo_names: Vec<String>;
+    let (prefix, partial, names) = match name {
+        "vol" => ("vol ", args, sounds),
+        "solo" => {
+            solo_names = sounds.iter().chain(groups).cloned().collect();
+            ("solo ", args, &solo_names[..])
+        }
+        "preset" => match args.strip_prefix("load ") {
+            Some(partial) => ("preset load ", partial, presets),
+            None if "load".starts_with(args) => return vec!["preset load ".to_string()],
+            None => return vec![],
+        },
+        _ => return vec![],
+    };
+    let partial = partial.to_lowercase();
+    names
+        .iter()
+        .filter(|name| name.to_lowercase().starts_with(&partial))
+        .map(|name| format!("{}{}{}", prefix, name, if prefix == "vol " { " " } else { "" }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn commands_parse() {
+        assert_eq!(Command::parse(":vol Heavy Rain 40"), Ok(Command::Volume { sound: "Heavy Rain".to_string(), volume: 0.4 }));
+        assert_eq!(Command::parse("volume rain 100%"), Ok(Command::Volume { sound: "rain".to_string(), volume: 1.0 }));
+        assert_eq!(Command::parse("  master 0 "), Ok(Command::Master(0.0)));
+        assert_eq!(Command::parse("solo Nature"), Ok(Command::Solo("Nature".to_string())));
+        assert_eq!(Command::parse("preset load Deep Focus"), Ok(Command::LoadPreset("Deep Focus".to_string())));
+        assert_eq!(Command::parse("timer 1h30m"), Ok(Command::Timer(Some(Duration::from_secs(5400)))));
+        assert_eq!(Command::parse("timer off"), Ok(Command::Timer(None)));
+        assert_eq!(Command::parse("add ~/sounds/rain.ogg"), Ok(Command::Add("~/sounds/rain.ogg".to_string())));
+        assert_eq!(Command::parse("export"), Ok(Command::Export { file: None, with_files: false }));
+        assert_eq!(Command::parse("export --with-files ~/cafe.toml"), Ok(Command::Export { file: Some("~/cafe.toml".to_string()), with_files: true }));
+        assert_eq!(Command::parse("export --with-filesx"), Ok(Command::Export { file: Some("--with-filesx".to_string()), with_files: false }));
+    }
+
+    #[test]
+    fn bad_commands_are_rejected() {
+        for line in ["", ":", "fly", "vol", "vol 40", "vol rain", "vol rain 101", "vol rain -1", "master loud", "solo", "preset", "preset load", "preset save x", "timer", "timer soon", "add"] {
+            assert!(Command::parse(line).is_err(), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn percents_go_from_0_to_100() {
+        assert_eq!(parse_percent("40"), Ok(0.4));
+        assert_eq!(parse_percent("12.5%"), Ok(0.125));
+        assert!(parse_percent("100.1").is_err());
+        assert!(parse_percent("%").is_err());
+        assert!(parse_percent("NaN").is_err());
+    }
+
+    #[test]
+    fn durations_parse() {
+        assert_eq!(parse_duration("45"), Ok(Duration::from_secs(45 * 60)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("1m1m"), Ok(Duration::from_secs(120)));
+        // Too long for a timer, which the app reports, but a duration
+        assert_eq!(parse_duration("18000000000000000000s"), Ok(Duration::from_secs(18_000_000_000_000_000_000)));
+        for text in ["", "0s", "h", "30x", "30m5", "1.5h", "-5m"] {
+            assert!(parse_duration(text).is_err(), "{:?}", text);
+        }
+        // Overflows of u64 seconds
+        for text in ["99999999999999999999", "307445734561825861", "307445734561825861m", "5124095576030432h", "18446744073709551615s1s"] {
+            assert!(parse_duration(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn durations_format() {
+        assert_eq!(format_duration(Duration::from_secs(270)), "4:30");
+        assert_eq!(format_duration(Duration::from_secs(3900)), "1:05:00");
+        assert_eq!(format_duration(Duration::ZERO), "0:00");
+    }
+
+    #[test]
+    fn completion() {
+        let sounds = names(&["Rain", "Rainforest", "Wind"]);
+        let groups = names(&["Nature"]);
+        let presets = names(&["Night"]);
+        assert_eq!(complete("", &sounds, &groups, &presets).len(), COMMANDS.len());
+        assert_eq!(complete("p", &sounds, &groups, &presets), names(&["preset "]));
+        assert_eq!(complete("vol ra", &sounds, &groups, &presets), names(&["vol Rain ", "vol Rainforest "]));
+        assert_eq!(complete("solo n", &sounds, &groups, &presets), names(&["solo Nature"]));
+        assert_eq!(complete("preset l", &sounds, &groups, &presets), names(&["preset load "]));
+        assert_eq!(complete("preset load n", &sounds, &groups, &presets), names(&["preset load Night"]));
+        assert_eq!(complete("timer 3", &sounds, &groups, &presets), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,143 @@
+use ratatui::{buffer::Buffer, crossterm::event::KeyEvent, layout::Rect, style::{Style, Stylize}, text::{Line, Span}, widgets::Widget};
+use crate::components::input::Input;
+use crate::{keymap::Action, theme};
+
+/// Number of commands kept in the history.
+const HISTORY_LIMIT: usize = 50;
+
+/// The `:` line at the bottom of the screen. Entered commands are kept so
+/// Up and Down can bring them back.
+pub struct CommandLine {
+    input: Input,
+    opened: bool,
+    history: Vec<String>,
+    /// Entry of `history` shown while browsing it, `None` for the line being typed.
+    history_index: Option<usize>,
+    /// Line being typed before browsing the history, restored past the last entry.
+    draft: String,
+    /// Candidates of the last Tab, cycled through by the next ones.
+    completions: Vec<String>,
+    completion_index: usize,
+}
+
+impl CommandLine {
+    pub fn new() -> Self {
+        CommandLine {
+            input: Input::new(String::new(), 255, true),
+            opened: false,
+            history: vec![],
+            history_index: None,
+            draft: String::new(),
+            completions: vec![],
+            completion_index: 0,
+        }
+    }
+
+    pub fn get_opened(&self) -> bool {
+        self.opened
+    }
+
+    pub fn open(&mut self) {
+        self.opened = true;
+        self.input.clear();
+        self.history_index = None;
+        self.completions.clear();
+    }
+
+    /// Opens the line with `line` already typed, for the command to be
+    /// finished by the user.
+    pub fn open_with(&mut self, line: &str) {
+        self.open();
+        self.input.set_text(line);
+    }
+
+    pub fn close(&mut self) {
+        self.opened = false;
+        self.input.clear();
+    }
+
+    /// Closes the line and returns the command typed, adding it to the history.
+    pub fn submit(&mut self) -> String {
+        let line = self.input.get_text().trim().to_string();
+        self.close();
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > HISTORY_LIMIT {
+                self.history.remove(0);
+            }
+        }
+        line
+    }
+
+    /// Replaces the line with the next candidate. A new list of candidates is
+    /// only asked for when the line changed since the last Tab.
+    pub fn complete(&mut self, candidates: impl FnOnce(&str) -> Vec<String>) {
+        if self.completions.get(self.completion_index).is_some_and(|current| current == self.input.get_text()) {
+            self.completion_index = (self.completion_index + 1) % self.completions.len();
+        } else {
+            self.completions = candidates(self.input.get_text());
+            self.completion_index = 0;
+        }
+        if let Some(completion) = self.completions.get(self.completion_index) {
+            self.input.set_text(completion);
+        }
+    }
+
+    fn browse_history(&mut self, delta: isize) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = match (self.history_index, delta < 0) {
+            (None, true) => {
+                self.draft = self.input.get_text().to_string();
+                self.history.len() - 1
+            }
+            (None, false) => return,
+            (Some(index), true) => index.saturating_sub(1),
+            (Some(index), false) if index + 1 < self.history.len() => index + 1,
+            (Some(_), false) => {
+                self.history_index = None;
+                let draft = std::mem::take(&mut self.draft);
+                self.input.set_text(&draft);
+                return;
+            }
+        };
+        self.history_index = Some(index);
+        self.input.set_text(&self.history[index]);
+    }
+
+    pub fn handle_action(&mut self, action: Action) {
+        match action {
+            Action::HistoryPrevious => self.browse_history(-1),
+            Action::HistoryNext => self.browse_history(1),
+            Action::CloseCommand => self.close(),
+            _ => {}
+        }
+    }
+
+    pub fn handle_key_event(&mut self, key_event: KeyEvent) {
+        self.completions.clear();
+        self.input.handle_key_event(key_event);
+    }
+}
+
+impl Widget for &CommandLine {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let text = self.input.get_text();
+        let (before, after) = text.split_at(
+            text.char_indices().nth(self.input.get_character_index()).map_or(text.len(), |(i, _)| i),
+        );
+        let mut spans = vec![Span::raw(":").bold(), Span::raw(before)];
+        let mut after = after.chars();
+        let cursor = after.next().map_or(" ".to_string(), String::from);
+        spans.push(Span::styled(cursor, Style::default().reversed()));
+        spans.push(Span::raw(after.as_str().to_string()));
+        if self.completions.len() > 1 {
+            spans.push(Span::styled(
+                format!("  ({}/{})", self.completion_index + 1, self.completions.len()),
+                theme::current().muted,
+            ));
+        }
+        Line::from(spans).render(area, buf);
+    }
+}
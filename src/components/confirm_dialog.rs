@@ -0,0 +1,104 @@
+use ratatui::{buffer::Buffer, crossterm::event::{KeyCode, KeyEvent}, layout::{Alignment, Constraint, Direction, Layout, Rect}, style::Stylize, symbols::border, text::{Line, Span}, widgets::{Block, Clear, Paragraph, Widget, Wrap}};
+use crate::theme;
+
+/// Modal yes/no question guarding an action of type `A`. The action is held
+/// while the dialog is open and handed back once the user confirms.
+pub struct ConfirmDialog<A> {
+    title: String,
+    message: String,
+    pending: Option<A>,
+    yes_selected: bool,
+}
+
+impl<A> ConfirmDialog<A> {
+    pub fn new() -> Self {
+        ConfirmDialog {
+            title: String::new(),
+            message: String::new(),
+            pending: None,
+            yes_selected: false,
+        }
+    }
+
+    /// Asks `message` before running `action`. "No" is selected at first, so
+    /// a stray Enter does not confirm.
+    pub fn open(&mut self, title: &str, message: String, action: A) {
+        self.title = format!(" {} ", title);
+        self.message = message;
+        self.pending = Some(action);
+        self.yes_selected = false;
+    }
+
+    pub fn get_opened(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn close(&mut self) {
+        self.pending = None;
+    }
+
+    /// Returns the pending action when the key confirms it. Any answer closes
+    /// the dialog.
+    pub fn handle_key_event(&mut self, key_event: KeyEvent) -> Option<A> {
+        match key_event.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => self.pending.take(),
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                self.close();
+                None
+            }
+            KeyCode::Left | KeyCode::Right | KeyCode::Tab | KeyCode::BackTab => {
+                self.yes_selected = !self.yes_selected;
+                None
+            }
+            KeyCode::Enter => {
+                let action = self.pending.take();
+                if self.yes_selected { action } else { None }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<A> Widget for &ConfirmDialog<A> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let div_vert = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Fill(1), Constraint::Length(7), Constraint::Fill(1)])
+            .split(area);
+        let div_vert_hor = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Fill(1), Constraint::Max(50), Constraint::Fill(1)])
+            .split(div_vert[1]);
+        let area = div_vert_hor[1];
+
+        Clear.render(area, buf);
+        let block = Block::bordered()
+            .title(self.title.as_str().bold())
+            .title_alignment(Alignment::Center)
+            .border_set(border::ROUNDED)
+            .style(theme::current().popup);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Fill(1), Constraint::Length(1)])
+            .margin(1)
+            .split(inner);
+        Paragraph::new(self.message.as_str())
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true })
+            .render(chunks[0], buf);
+
+        let button = |label: &'static str, selected: bool| {
+            if selected {
+                Span::styled(label, theme::current().selected)
+            } else {
+                Span::raw(label)
+            }
+        };
+        Line::from(vec![button(" [y] Yes ", self.yes_selected), Span::raw("   "), button(" [n] No ", !self.yes_selected)])
+            .alignment(Alignment::Center)
+            .render(chunks[1], buf);
+    }
+}
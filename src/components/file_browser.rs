@@ -0,0 +1,229 @@
+use std::{env, fs, path::{Path, PathBuf}};
+
+use ratatui::{buffer::Buffer, crossterm::event::{KeyCode, KeyEvent}, layout::{Alignment, Constraint, Direction, Layout, Rect}, style::Stylize, symbols::border, text::Line, widgets::{Block, Clear, Paragraph, Widget}};
+use rodio::OutputStreamHandle;
+use crate::{app::{resolve_sound_path, DEFAULT_VOLUME}, components::sound::{is_supported_audio, Sound}, theme};
+
+struct BrowserEntry {
+    path: PathBuf,
+    label: String,
+    is_dir: bool,
+}
+
+pub struct FileBrowser {
+    current_dir: PathBuf,
+    entries: Vec<BrowserEntry>,
+    selected: usize,
+    opened: bool,
+    preview: Option<Sound>,
+    stream_handle: Option<OutputStreamHandle>,
+}
+
+impl FileBrowser {
+    pub fn new(stream_handle: Option<OutputStreamHandle>) -> Self {
+        FileBrowser {
+            current_dir: PathBuf::from("."),
+            entries: vec![],
+            selected: 0,
+            opened: false,
+            preview: None,
+            stream_handle,
+        }
+    }
+
+    pub fn get_opened(&self) -> bool {
+        self.opened
+    }
+
+    /// Opens the browser in the directory of `start`, or in the working
+    /// directory when `start` does not point anywhere useful.
+    pub fn open(&mut self, start: &str) {
+        let start = PathBuf::from(resolve_sound_path(start));
+        let dir = if start.is_dir() {
+            Some(start)
+        } else {
+            start.parent().filter(|p| p.is_dir()).map(Path::to_path_buf)
+        };
+        let dir = dir
+            .filter(|d| !d.as_os_str().is_empty())
+            .or_else(|| env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("."));
+        self.change_dir(dir);
+        self.opened = true;
+    }
+
+    pub fn close(&mut self) {
+        self.stop_preview();
+        self.opened = false;
+    }
+
+    fn change_dir(&mut self, dir: PathBuf) {
+        self.stop_preview();
+        self.current_dir = dir.canonicalize().unwrap_or(dir);
+        self.entries.clear();
+        self.selected = 0;
+
+        if let Some(parent) = self.current_dir.parent() {
+            self.entries.push(BrowserEntry { path: parent.to_path_buf(), label: "../".to_string(), is_dir: true });
+        }
+        let mut children: Vec<BrowserEntry> = match fs::read_dir(&self.current_dir) {
+            Ok(read_dir) => read_dir
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let path = entry.path();
+                    let name = entry.file_name().into_string().ok()?;
+                    let is_dir = path.is_dir();
+                    if name.starts_with('.') || !(is_dir || is_supported_audio(&path)) {
+                        return None;
+                    }
+                    let label = if is_dir { format!("{}/", name) } else { name };
+                    Some(BrowserEntry { path, label, is_dir })
+                })
+                .collect(),
+            Err(_) => vec![],
+        };
+        // Directories first, then files, each alphabetically
+        children.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.label.to_lowercase().cmp(&b.label.to_lowercase())));
+        self.entries.extend(children);
+    }
+
+    fn select(&mut self, index: usize) {
+        if index != self.selected {
+            self.stop_preview();
+        }
+        self.selected = index.min(self.entries.len().saturating_sub(1));
+    }
+
+    fn stop_preview(&mut self) {
+        self.preview = None;
+    }
+
+    fn toggle_preview(&mut self) {
+        if self.preview.take().is_some() {
+            return;
+        }
+        let (Some(entry), Some(handle)) = (self.entries.get(self.selected), self.stream_handle.as_ref()) else {
+            return;
+        };
+        if entry.is_dir {
+            return;
+        }
+        let mut sound = Sound::new(entry.path.to_string_lossy().to_string(), DEFAULT_VOLUME, handle);
+        sound.set_volume(DEFAULT_VOLUME, 1.0);
+        sound.play();
+        self.preview = Some(sound);
+    }
+
+    /// Returns the chosen file once the user picks one.
+    pub fn handle_key_event(&mut self, key_event: KeyEvent) -> Option<String> {
+        match key_event.code {
+            KeyCode::Esc => self.close(),
+            KeyCode::Up => self.select(self.selected.saturating_sub(1)),
+            KeyCode::Down => self.select(self.selected + 1),
+            KeyCode::PageUp => self.select(self.selected.saturating_sub(10)),
+            KeyCode::PageDown => self.select(self.selected + 10),
+            KeyCode::Left | KeyCode::Backspace => {
+                if let Some(parent) = self.current_dir.parent() {
+                    self.change_dir(parent.to_path_buf());
+                }
+            }
+            KeyCode::Char(' ') => self.toggle_preview(),
+            KeyCode::Enter | KeyCode::Right => {
+                let entry = self.entries.get(self.selected)?;
+                if entry.is_dir {
+                    let dir = entry.path.clone();
+                    self.change_dir(dir);
+                } else if key_event.code == KeyCode::Enter {
+                    let path = entry.path.to_string_lossy().to_string();
+                    self.close();
+                    return Some(path);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Completes the last component of `text` against the directories and audio
+/// files next to it. Returns `None` when there is nothing to add.
+pub fn complete_path(text: &str) -> Option<String> {
+    let (dir_part, prefix) = match text.rfind('/') {
+        Some(i) => (&text[..=i], &text[i + 1..]),
+        None => ("", text),
+    };
+    let dir = resolve_sound_path(if dir_part.is_empty() { "." } else { dir_part });
+    let matches: Vec<(String, bool)> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let path = entry.path();
+            let is_dir = path.is_dir();
+            let hidden = name.starts_with('.') && !prefix.starts_with('.');
+            (name.starts_with(prefix) && !hidden && (is_dir || is_supported_audio(&path))).then_some((name, is_dir))
+        })
+        .collect();
+
+    let completed = match matches.as_slice() {
+        [] => return None,
+        [(name, is_dir)] => format!("{}{}{}", dir_part, name, if *is_dir { "/" } else { "" }),
+        [(first, _), rest @ ..] => {
+            let common = rest.iter().fold(first.clone(), |common, (name, _)| {
+                common.chars().zip(name.chars()).take_while(|(a, b)| a == b).map(|(a, _)| a).collect()
+            });
+            format!("{}{}", dir_part, common)
+        }
+    };
+    (completed != text).then_some(completed)
+}
+
+impl Widget for &FileBrowser {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let div_vert = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Fill(1), Constraint::Percentage(70), Constraint::Fill(1)])
+            .split(area);
+        let div_vert_hor = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Fill(1), Constraint::Percentage(70), Constraint::Fill(1)])
+            .split(div_vert[1]);
+        let area = div_vert_hor[1];
+
+        Clear.render(area, buf);
+        let block = Block::bordered()
+            .title(Line::from(format!(" {} ", self.current_dir.display())).bold())
+            .title_bottom(Line::from(vec![
+                " [Enter]".bold(), " Open ".into(),
+                "[Space]".bold(), " Preview ".into(),
+                "[Backspace]".bold(), " Up ".into(),
+                "[Esc]".bold(), " Close ".into(),
+            ]).alignment(Alignment::Center))
+            .border_set(border::ROUNDED)
+            .style(theme::current().popup);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        if self.entries.is_empty() {
+            Paragraph::new("No audio files here")
+                .style(theme::current().highlight)
+                .alignment(Alignment::Center)
+                .render(inner, buf);
+            return;
+        }
+
+        let height = inner.height as usize;
+        let offset = (self.selected + 1).saturating_sub(height);
+        for (i, entry) in self.entries.iter().skip(offset).take(height).enumerate() {
+            let index = offset + i;
+            let theme = theme::current();
+            let mut style = if entry.is_dir { theme.header } else { theme.inactive };
+            if index == self.selected {
+                style = style.patch(theme.selected);
+            }
+            let marker = if index == self.selected && self.preview.is_some() { "▶ " } else { "  " };
+            let row = Rect::new(inner.x, inner.y + i as u16, inner.width, 1);
+            Paragraph::new(format!("{}{}", marker, entry.label)).style(style).render(row, buf);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use ratatui::{buffer::Buffer, crossterm::event::{KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind}, layout::{Alignment, Constraint, Direction, Layout, Margin, Position, Rect}, style::{Style, Stylize}, symbols::border, text::{Line, Span}, widgets::{Block, Clear, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget, Tabs, Widget}};
+use crate::{keymap::{format_sequence, Context, Keymap}, theme};
+
+/// Lines moved by a turn of the mouse wheel.
+const WHEEL_LINES: isize = 3;
+
+/// The actions of a context with the keys bound to them. The lines are built
+/// from the keymap in use each time the overlay opens, so rebinding a key in
+/// `keys.toml` or switching presets shows up here as well. It opens on the
+/// context that had the focus and Tab goes through the others.
+pub struct HelpOverlay {
+    opened: bool,
+    /// Every context with its lines.
+    sections: Vec<(Context, Vec<Line<'static>>)>,
+    section: usize,
+    scroll: usize,
+}
+
+impl HelpOverlay {
+    pub fn new() -> Self {
+        HelpOverlay { opened: false, sections: vec![], section: 0, scroll: 0 }
+    }
+
+    pub fn get_opened(&self) -> bool {
+        self.opened
+    }
+
+    pub fn open(&mut self, keymap: &Keymap, context: Context) {
+        self.sections = Context::ALL.iter().map(|context| (*context, help_lines(keymap, *context))).collect();
+        self.section = Context::ALL.iter().position(|c| *c == context).unwrap_or(0);
+        self.scroll = 0;
+        self.opened = true;
+    }
+
+    pub fn close(&mut self) {
+        self.opened = false;
+    }
+
+    fn lines(&self) -> &[Line<'static>] {
+        self.sections.get(self.section).map_or(&[], |(_, lines)| lines.as_slice())
+    }
+
+    /// Scrolls by `delta` lines, without going past the end of the lines
+    /// once the overlay is drawn over `area`.
+    fn scroll_by(&mut self, delta: isize, area: Rect) {
+        let height = body_area(popup_area(area)).height as usize;
+        let max_scroll = self.lines().len().saturating_sub(height);
+        self.scroll = self.scroll.saturating_add_signed(delta).min(max_scroll);
+    }
+
+    fn switch_section(&mut self, forward: bool) {
+        let count = self.sections.len().max(1);
+        self.section = if forward { (self.section + 1) % count } else { (self.section + count - 1) % count };
+        self.scroll = 0;
+    }
+
+    /// Keys scroll, switch context or close the overlay, which is drawn over
+    /// `area`. Other keys are ignored.
+    pub fn handle_key_event(&mut self, key_event: KeyEvent, area: Rect) {
+        let page = body_area(popup_area(area)).height.saturating_sub(1).max(1) as isize;
+        match key_event.code {
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_by(-1, area),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_by(1, area),
+            KeyCode::PageUp => self.scroll_by(-page, area),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll_by(page, area),
+            KeyCode::Home | KeyCode::Char('g') => self.scroll_by(isize::MIN, area),
+            KeyCode::End | KeyCode::Char('G') => self.scroll_by(isize::MAX, area),
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') => self.switch_section(true),
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => self.switch_section(false),
+            KeyCode::Esc | KeyCode::Enter | KeyCode::F(1) | KeyCode::Char('q') | KeyCode::Char('?') => self.close(),
+            _ => {}
+        }
+    }
+
+    /// The wheel scrolls and a click outside the overlay closes it.
+    pub fn handle_mouse_event(&mut self, mouse_event: MouseEvent, area: Rect) {
+        match mouse_event.kind {
+            MouseEventKind::ScrollUp => self.scroll_by(-WHEEL_LINES, area),
+            MouseEventKind::ScrollDown => self.scroll_by(WHEEL_LINES, area),
+            MouseEventKind::Down(MouseButton::Left)
+                if !popup_area(area).contains(Position::new(mouse_event.column, mouse_event.row)) => self.close(),
+            _ => {}
+        }
+    }
+}
+
+/// A title and a line per action of `context`, followed by the bindings of
+/// the context it falls back to.
+fn help_lines(keymap: &Keymap, context: Context) -> Vec<Line<'static>> {
+    let mut sections = vec![];
+    let mut next = Some(context);
+    while let Some(context) = next {
+        let entries: Vec<(String, &str)> = keymap
+            .help(context)
+            .into_iter()
+            .map(|(description, keys)| {
+                let keys = if keys.is_empty() {
+                    "unbound".to_string()
+                } else {
+                    keys.iter().map(|keys| format_sequence(keys)).collect::<Vec<String>>().join(" / ")
+                };
+                (keys, description)
+            })
+            .collect();
+        sections.push((context, entries));
+        next = context.fallback();
+    }
+    let width = sections.iter().flat_map(|(_, entries)| entries).map(|(keys, _)| keys.chars().count()).max().unwrap_or(0);
+    let mut lines = vec![];
+    for (context, entries) in sections {
+        if !lines.is_empty() {
+            lines.push(Line::default());
+        }
+        lines.push(Line::from(Span::styled(context.title(), theme::current().header)));
+        for (keys, description) in entries {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{:>width$}  ", keys, width = width), Style::default().bold()),
+                Span::raw(description.to_string()),
+            ]));
+        }
+    }
+    lines
+}
+
+/// Where the overlay is drawn over `area`.
+fn popup_area(area: Rect) -> Rect {
+    let div_vert = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Fill(1), Constraint::Percentage(90), Constraint::Fill(1)])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![Constraint::Fill(1), Constraint::Max(80), Constraint::Fill(1)])
+        .split(div_vert[1])[1]
+}
+
+/// Where the lines are drawn in the overlay drawn in `area`, below the tabs
+/// of the contexts.
+fn body_area(area: Rect) -> Rect {
+    let inner = area.inner(Margin::new(1, 1));
+    Rect { y: inner.y + 2, height: inner.height.saturating_sub(2), ..inner }
+}
+
+impl Widget for &HelpOverlay {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let area = popup_area(area);
+        Clear.render(area, buf);
+        let block = Block::bordered()
+            .title(" Help ".bold())
+            .title_alignment(Alignment::Center)
+            .title_bottom(Line::from(vec![
+                " [↑↓]".bold(),
+                " Scroll ".into(),
+                " [Tab]".bold(),
+                " Other keys ".into(),
+                " [Esc]".bold(),
+                " Close ".into(),
+            ]).alignment(Alignment::Center))
+            .border_set(border::ROUNDED)
+            .style(theme::current().popup);
+        block.render(area, buf);
+
+        let inner = area.inner(Margin::new(1, 1));
+        let titles = self.sections.iter().map(|(context, _)| context.name());
+        Tabs::new(titles)
+            .select(self.section)
+            .highlight_style(theme::current().selected)
+            .render(Rect { height: inner.height.min(1), ..inner }, buf);
+
+        let body = body_area(area);
+        let lines = self.lines();
+        let scroll = self.scroll.min(lines.len().saturating_sub(body.height as usize));
+        let scrolling = lines.len() > body.height as usize;
+        let text = Rect { width: body.width.saturating_sub(scrolling as u16 * 2), ..body };
+        Paragraph::new(lines.to_vec()).scroll((scroll as u16, 0)).render(text, buf);
+        if scrolling {
+            let mut state = ScrollbarState::new(lines.len().saturating_sub(body.height as usize)).position(scroll);
+            Scrollbar::new(ScrollbarOrientation::VerticalRight).render(body, buf, &mut state);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::path::Path;
+
+use ratatui::{buffer::Buffer, crossterm::event::{KeyCode, KeyEvent}, layout::{Alignment, Constraint, Direction, Layout, Rect}, style::Stylize, symbols::border, text::Line, widgets::{Block, Clear, Paragraph, Widget}};
+use crate::{app::resolve_sound_path, components::{file_browser::complete_path, input::Input}, config::SoundConfig, import::{append_sounds, scan_directory}, theme};
+
+enum ImportStage {
+    Directory,
+    Checklist,
+}
+
+pub struct ImportPopup {
+    input: Input,
+    candidates: Vec<(SoundConfig, bool)>,
+    selected: usize,
+    opened: bool,
+    stage: ImportStage,
+    message: Option<String>,
+    imported: bool,
+}
+
+impl ImportPopup {
+    pub fn new() -> Self {
+        ImportPopup {
+            input: Input::new(" Directory ".to_string(), 255, true),
+            candidates: vec![],
+            selected: 0,
+            opened: false,
+            stage: ImportStage::Directory,
+            message: None,
+            imported: false,
+        }
+    }
+
+    pub fn get_opened(&self) -> bool {
+        self.opened
+    }
+
+    pub fn set_opened(&mut self, opened: bool) {
+        self.opened = opened;
+    }
+
+    pub fn clear(&mut self) {
+        self.input.clear();
+        self.candidates.clear();
+        self.selected = 0;
+        self.stage = ImportStage::Directory;
+        self.message = None;
+    }
+
+    /// Whether sounds were appended to the library since the last call.
+    pub fn take_imported(&mut self) -> bool {
+        std::mem::take(&mut self.imported)
+    }
+
+    fn scan(&mut self) {
+        let dir = resolve_sound_path(self.input.get_text());
+        match scan_directory(Path::new(&dir)) {
+            Ok(result) if result.sounds.is_empty() => {
+                self.message = Some(format!("No new audio files found ({} already imported)", result.already_imported));
+            }
+            Ok(result) => {
+                self.candidates = result.sounds.into_iter().map(|sound| (sound, true)).collect();
+                self.selected = 0;
+                self.stage = ImportStage::Checklist;
+                self.message = (result.already_imported > 0)
+                    .then(|| format!("{} file(s) already imported were skipped", result.already_imported));
+            }
+            Err(e) => self.message = Some(format!("Cannot read {}: {}", dir, e)),
+        }
+    }
+
+    fn import_checked(&mut self) {
+        let sounds: Vec<SoundConfig> = self.candidates
+            .iter()
+            .filter(|(_, checked)| *checked)
+            .map(|(sound, _)| sound.clone())
+            .collect();
+        if sounds.is_empty() {
+            self.message = Some("No sounds selected".to_string());
+            return;
+        }
+        match append_sounds(&sounds) {
+            Ok(()) => {
+                self.imported = true;
+                self.set_opened(false);
+                self.clear();
+            }
+            Err(e) => self.message = Some(format!("Error writing to sounds file: {}", e)),
+        }
+    }
+
+    pub fn handle_key_event(&mut self, key_event: KeyEvent) {
+        if key_event.code == KeyCode::Esc {
+            self.set_opened(false);
+            self.clear();
+            return;
+        }
+        match self.stage {
+            ImportStage::Directory => match key_event.code {
+                KeyCode::Tab => {
+                    if let Some(completed) = complete_path(self.input.get_text()) {
+                        self.input.set_text(&completed);
+                    }
+                }
+                KeyCode::Enter => self.scan(),
+                _ => self.input.handle_key_event(key_event),
+            },
+            ImportStage::Checklist => match key_event.code {
+                KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Down => self.selected = (self.selected + 1).min(self.candidates.len() - 1),
+                KeyCode::Char(' ') => {
+                    if let Some((_, checked)) = self.candidates.get_mut(self.selected) {
+                        *checked = !*checked;
+                    }
+                }
+                KeyCode::Char('a') => {
+                    let check = !self.candidates.iter().all(|(_, checked)| *checked);
+                    for (_, checked) in &mut self.candidates {
+                        *checked = check;
+                    }
+                }
+                KeyCode::Enter => self.import_checked(),
+                _ => {}
+            },
+        }
+    }
+}
+
+impl Widget for &ImportPopup {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let div_vert = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Fill(1), Constraint::Percentage(70), Constraint::Fill(1)])
+            .split(area);
+        let div_vert_hor = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Fill(1), Constraint::Percentage(70), Constraint::Fill(1)])
+            .split(div_vert[1]);
+        let area = div_vert_hor[1];
+
+        Clear.render(area, buf);
+        let instructions = match self.stage {
+            ImportStage::Directory => Line::from(vec![
+                " [Tab]".bold(), " Complete ".into(),
+                "[Enter]".bold(), " Scan ".into(),
+                "[Esc]".bold(), " Close ".into(),
+            ]),
+            ImportStage::Checklist => Line::from(vec![
+                " [Space]".bold(), " Toggle ".into(),
+                "[a]".bold(), " All ".into(),
+                "[Enter]".bold(), " Import ".into(),
+                "[Esc]".bold(), " Close ".into(),
+            ]),
+        };
+        let block = Block::bordered()
+            .title(" Import Sounds ".bold())
+            .title_alignment(Alignment::Center)
+            .title_bottom(instructions.alignment(Alignment::Center))
+            .border_set(border::ROUNDED)
+            .style(theme::current().popup);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Fill(1), Constraint::Length(1)])
+            .split(inner);
+        if let Some(message) = &self.message {
+            Line::from(message.as_str())
+                .style(theme::current().highlight)
+                .alignment(Alignment::Center)
+                .render(chunks[1], buf);
+        }
+
+        match self.stage {
+            ImportStage::Directory => {
+                let input_area = Rect::new(chunks[0].x, chunks[0].y, chunks[0].width, 3.min(chunks[0].height));
+                self.input.render(input_area, buf);
+            }
+            ImportStage::Checklist => {
+                let height = chunks[0].height as usize;
+                let offset = (self.selected + 1).saturating_sub(height);
+                for (i, (sound, checked)) in self.candidates.iter().skip(offset).take(height).enumerate() {
+                    let index = offset + i;
+                    let mut style = theme::current().inactive;
+                    if index == self.selected {
+                        style = style.patch(theme::current().selected);
+                    }
+                    let row = Rect::new(chunks[0].x, chunks[0].y + i as u16, chunks[0].width, 1);
+                    let checkbox = if *checked { "[x]" } else { "[ ]" };
+                    Paragraph::new(format!("{} {} {}  {}", checkbox, sound.icon, sound.name, sound.file))
+                        .style(style)
+                        .render(row, buf);
+                }
+            }
+        }
+    }
+}
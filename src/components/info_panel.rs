@@ -0,0 +1,77 @@
+use ratatui::{buffer::Buffer, layout::{Alignment, Rect}, style::Stylize, symbols::border, text::{Line, Span}, widgets::{Block, Padding, Paragraph, Widget, Wrap}};
+use crate::{components::sound_item::SoundItem, theme};
+
+/// Details of the selected sound: its file, the probed audio format and the
+/// credits from `sounds.toml`.
+pub struct InfoPanel<'a> {
+    sound: Option<&'a SoundItem>,
+}
+
+impl<'a> InfoPanel<'a> {
+    pub fn new(sound: Option<&'a SoundItem>) -> Self {
+        InfoPanel { sound }
+    }
+}
+
+fn field<'a>(label: &'a str, value: String) -> Line<'a> {
+    Line::from(vec![Span::styled(format!("{:<12}", label), theme::current().label), Span::raw(value)])
+}
+
+fn or_unknown(value: Option<&str>) -> String {
+    value.unwrap_or("unknown").to_string()
+}
+
+impl Widget for InfoPanel<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .border_set(border::THICK)
+            .border_style(theme::current().border)
+            .title(Line::from(" Info ".bold()))
+            .title_alignment(Alignment::Center)
+            .padding(Padding::horizontal(1));
+
+        let Some(sound) = self.sound else {
+            Paragraph::new("Select a sound to see its details")
+                .style(theme::current().highlight)
+                .wrap(Wrap { trim: true })
+                .block(block)
+                .render(area, buf);
+            return;
+        };
+
+        let mut lines = vec![
+            Line::from(format!("{} {}", sound.get_icon(), sound.get_name()).bold()),
+            Line::default(),
+            field("Category", sound.get_category().unwrap_or("-").to_string()),
+            field("Tags", if sound.get_tags().is_empty() { "-".to_string() } else { sound.get_tags().join(", ") }),
+            field("Favorite", if sound.is_favorite() { "yes" } else { "no" }.to_string()),
+            Line::default(),
+            field("File", sound.get_path().to_string()),
+        ];
+        match sound.get_info() {
+            Some(info) => {
+                lines.push(field("Codec", info.format.to_string()));
+                lines.push(field("Channels", info.channels.to_string()));
+                lines.push(field("Sample rate", format!("{} Hz", info.sample_rate)));
+                let duration = info.duration.map_or_else(
+                    || "unknown".to_string(),
+                    |duration| format!("{}:{:02}", duration.as_secs() / 60, duration.as_secs() % 60),
+                );
+                lines.push(field("Duration", duration));
+            }
+            None => {
+                let error = sound.get_error().unwrap_or_default().to_string();
+                lines.push(Line::from(format!("⚠ {}", error)).style(theme::current().error));
+            }
+        }
+        lines.push(Line::default());
+        lines.push(field("Author", or_unknown(sound.get_author())));
+        lines.push(field("License", or_unknown(sound.get_license())));
+        lines.push(field("Source", or_unknown(sound.get_source_url())));
+
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(block)
+            .render(area, buf);
+    }
+}
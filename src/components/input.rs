@@ -1,4 +1,5 @@
-use ratatui::{crossterm::event::{KeyCode, KeyEvent}, style::{Color, Style, Stylize}, symbols::border, text::Text, widgets::{Block, Paragraph, Widget}};
+use crate::theme;
+use ratatui::{crossterm::event::{KeyCode, KeyEvent}, symbols::border, text::Text, widgets::{Block, Paragraph, Widget}};
 
 
 pub struct Input {
@@ -24,8 +25,29 @@ impl Input {
         &self.text
     }
 
+    /// Replaces the contents, truncated to `max_length`, and moves the cursor
+    /// to the end.
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.chars().take(self.max_length).collect();
+        self.character_index = self.text.chars().count();
+    }
+
+    pub fn get_character_index(&self) -> usize {
+        self.character_index
+    }
+
     pub fn set_character_index(&mut self, position: usize) {
-        self.character_index = position.clamp(0, self.text.len());
+        self.character_index = position.clamp(0, self.text.chars().count());
+    }
+
+    /// Byte offset of the character at `index`, since `character_index` counts
+    /// characters and icons are usually multi-byte glyphs.
+    fn byte_index(&self, index: usize) -> usize {
+        self.text
+            .char_indices()
+            .map(|(i, _)| i)
+            .nth(index)
+            .unwrap_or(self.text.len())
     }
 
     pub fn set_active(&mut self, active: bool) {
@@ -56,16 +78,16 @@ impl Input {
     }
 
     fn enter_character(&mut self, character: char) {
-        if character == '\n' || self.text.len() >= self.max_length {
+        if character == '\n' || self.text.chars().count() >= self.max_length {
             return; // Ignore newline characters
         }
-        self.text.insert(self.character_index, character);
+        self.text.insert(self.byte_index(self.character_index), character);
         self.move_character_right();
     }
 
     fn delete_character(&mut self) {
         if self.character_index > 0 {
-            self.text.remove(self.character_index - 1);
+            self.text.remove(self.byte_index(self.character_index - 1));
             self.move_character_left();
         }
     }
@@ -83,20 +105,20 @@ impl Input {
 
 impl Widget for &Input {
     fn render(self, area: ratatui::layout::Rect, buf: &mut ratatui::buffer::Buffer) {
-        let min = self.character_index.saturating_sub(area.width as usize - 2).clamp(0, self.text.len());
-        let max = (self.character_index).clamp(0, self.text.len());
+        let min = self.character_index.saturating_sub(area.width as usize - 2);
+        let max = self.character_index.max(min);
 
         let name = self.name.clone();
-        let text = Text::from(self.get_text()[min..max].to_string())
-            .style(Style::default().fg(Color::White));
+        let text = Text::from(self.text.chars().skip(min).take(max - min).collect::<String>())
+            .style(theme::current().inactive);
         let block = Block::bordered()
             .title(name)
             .border_set(border::ROUNDED)
             .border_style({
                 if self.active {
-                    Style::default().fg(Color::Green)
+                    theme::current().focus
                 } else {
-                    Style::default().fg(Color::White)
+                    theme::current().border
                 }
             });
         let paragraph = Paragraph::new(text)
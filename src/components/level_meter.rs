@@ -0,0 +1,93 @@
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+use crate::analysis::{db_fraction, spectrum, Levels};
+use crate::components::volume_gauge::supports_unicode;
+use crate::theme;
+
+/// Width of the meter drawn in each row of the list.
+pub const METER_WIDTH: u16 = 8;
+
+/// Cells filled by one to seven eighths, from the bottom.
+pub const VERTICAL_EIGHTHS: [char; 7] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇'];
+/// Cells filled by one to seven eighths, from the left.
+pub const HORIZONTAL_EIGHTHS: [char; 7] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+/// Character of a cell filled by `eighths` eighths, none if empty. Without
+/// Unicode, cells at least half full get `half`.
+pub fn cell(eighths: u32, partial: &[char; 7], half: char, unicode: bool) -> Option<char> {
+    match (eighths, unicode) {
+        (0, _) => None,
+        (8.., true) => Some('█'),
+        (eighths, true) => Some(partial[eighths as usize - 1]),
+        (8.., false) => Some('#'),
+        (4.., false) => Some(half),
+        (_, false) => None,
+    }
+}
+
+/// A horizontal meter of the RMS level on a decibel scale, with a mark at
+/// the peak.
+pub struct LevelMeter {
+    levels: Levels,
+    style: Style,
+}
+
+impl LevelMeter {
+    pub fn new(levels: Levels, style: Style) -> Self {
+        LevelMeter { levels, style }
+    }
+}
+
+impl Widget for &LevelMeter {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.is_empty() || self.levels.peak <= 0.0 {
+            return;
+        }
+        let theme = theme::current();
+        let unicode = supports_unicode();
+        let width = area.width as f32;
+        let filled = (db_fraction(self.levels.rms) * width * 8.0).round() as u32;
+        let peak = ((db_fraction(self.levels.peak) * width) as u16).min(area.width - 1);
+        for i in 0..area.width {
+            let eighths = filled.saturating_sub(i as u32 * 8).min(8);
+            let position = (area.x + i, area.y);
+            if let Some(symbol) = cell(eighths, &HORIZONTAL_EIGHTHS, '=', unicode) {
+                buf[position].set_char(symbol).set_style(self.style.patch(theme.gauge));
+            } else if i == peak {
+                buf[position].set_char(if unicode { '▏' } else { '|' }).set_style(self.style.patch(theme.highlight));
+            }
+        }
+    }
+}
+
+/// Bars of the strength of frequency bands of a window of samples, low
+/// frequencies on the left, one band per column.
+pub struct Spectrum {
+    samples: Vec<f32>,
+    sample_rate: u32,
+}
+
+impl Spectrum {
+    pub fn new(samples: Vec<f32>, sample_rate: u32) -> Self {
+        Spectrum { samples, sample_rate }
+    }
+}
+
+impl Widget for &Spectrum {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.is_empty() {
+            return;
+        }
+        let style = theme::current().gauge;
+        let unicode = supports_unicode();
+        let bands = spectrum(&self.samples, self.sample_rate, area.width as usize);
+        for (x, band) in (area.x..area.right()).zip(bands) {
+            let filled = (band * area.height as f32 * 8.0).round() as u32;
+            for row in 0..area.height {
+                let eighths = filled.saturating_sub(row as u32 * 8).min(8);
+                if let Some(symbol) = cell(eighths, &VERTICAL_EIGHTHS, '.', unicode) {
+                    buf[(x, area.bottom() - 1 - row)].set_char(symbol).set_style(style);
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,15 @@
 pub mod sounds_block;
 pub mod sound_item;
 pub mod sound;
+pub mod info_panel;
 
 pub mod sound_add_popup;
 pub mod input;
+pub mod file_browser;
+pub mod import_popup;
+pub mod confirm_dialog;
+pub mod help_overlay;
+pub mod command_line;
+pub mod volume_gauge;
+pub mod level_meter;
+pub mod notifications;
@@ -0,0 +1,276 @@
+use std::{collections::VecDeque, fmt, time::{Duration, Instant}};
+
+use ratatui::{buffer::Buffer, crossterm::event::{KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind}, layout::{Alignment, Constraint, Direction, Layout, Margin, Position, Rect}, style::{Style, Stylize}, symbols::border, text::{Line, Span}, widgets::{Block, Clear, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget, Widget}};
+use crate::theme;
+
+/// Messages kept in the log; older ones are dropped.
+const LOG_LIMIT: usize = 200;
+/// Lines moved by a turn of the mouse wheel.
+const WHEEL_LINES: isize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// How long the message stays in the header. Problems stay longer.
+    fn duration(self) -> Duration {
+        match self {
+            Severity::Info => Duration::from_secs(4),
+            Severity::Warning => Duration::from_secs(8),
+            Severity::Error => Duration::from_secs(12),
+        }
+    }
+
+    fn style(self) -> Style {
+        let theme = theme::current();
+        match self {
+            Severity::Info => theme.inactive,
+            Severity::Warning => theme.highlight,
+            Severity::Error => theme.error,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub severity: Severity,
+    pub message: String,
+    pub time: Instant,
+}
+
+impl Notification {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Notification { severity, message: message.into(), time: Instant::now() }
+    }
+
+    pub fn info(message: impl Into<String>) -> Self {
+        Notification::new(Severity::Info, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Notification::new(Severity::Warning, message)
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Notification::new(Severity::Error, message)
+    }
+
+    /// The message as a line, with its severity unless it is only information.
+    pub fn to_line(&self) -> Line<'static> {
+        let style = self.severity.style();
+        match self.severity {
+            Severity::Info => Line::from(Span::styled(self.message.clone(), style)),
+            severity => Line::from(vec![
+                Span::styled(format!("{}: ", severity), style.bold()),
+                Span::styled(self.message.clone(), style),
+            ]),
+        }
+    }
+}
+
+/// `12s`, `5m` or `2h`: how long ago `time` was.
+fn format_age(time: Instant) -> String {
+    let seconds = time.elapsed().as_secs();
+    match seconds {
+        0..60 => format!("{}s", seconds),
+        60..3600 => format!("{}m", seconds / 60),
+        _ => format!("{}h", seconds / 3600),
+    }
+}
+
+/// Messages for the user. The latest one is shown in the header as a toast
+/// until it expires, and every message is kept in a log that opens as a
+/// scrollable popup.
+pub struct Notifications {
+    log: VecDeque<Notification>,
+    /// Whether the latest message was dismissed before it expired.
+    dismissed: bool,
+    log_opened: bool,
+    /// First line of the log shown, `None` to follow the newest messages.
+    scroll: Option<usize>,
+    /// Warnings and errors since the log was last opened.
+    unread: usize,
+}
+
+impl Notifications {
+    pub fn new() -> Self {
+        Notifications { log: VecDeque::new(), dismissed: false, log_opened: false, scroll: None, unread: 0 }
+    }
+
+    pub fn push(&mut self, notification: Notification) {
+        if notification.severity >= Severity::Warning {
+            self.unread += 1;
+        }
+        self.log.push_back(notification);
+        if self.log.len() > LOG_LIMIT {
+            self.log.pop_front();
+            self.scroll = self.scroll.map(|scroll| scroll.saturating_sub(1));
+        }
+        self.dismissed = false;
+    }
+
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.push(Notification::info(message));
+    }
+
+    pub fn warning(&mut self, message: impl Into<String>) {
+        self.push(Notification::warning(message));
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.push(Notification::error(message));
+    }
+
+    pub fn extend(&mut self, notifications: Vec<Notification>) {
+        for notification in notifications {
+            self.push(notification);
+        }
+    }
+
+    /// The latest message, while it has not expired or been dismissed.
+    pub fn get_toast(&self) -> Option<&Notification> {
+        self.log.back().filter(|latest| !self.dismissed && latest.time.elapsed() < latest.severity.duration())
+    }
+
+    /// Time left before the toast expires, so the header can be redrawn then.
+    pub fn get_toast_remaining(&self) -> Option<Duration> {
+        self.get_toast().map(|toast| toast.severity.duration().saturating_sub(toast.time.elapsed()))
+    }
+
+    pub fn get_unread(&self) -> usize {
+        self.unread
+    }
+
+    pub fn get_log_opened(&self) -> bool {
+        self.log_opened
+    }
+
+    pub fn open_log(&mut self) {
+        self.log_opened = true;
+        self.scroll = None;
+        self.dismissed = true;
+        self.unread = 0;
+    }
+
+    pub fn close_log(&mut self) {
+        self.log_opened = false;
+    }
+
+    fn max_scroll(&self, area: Rect) -> usize {
+        self.log.len().saturating_sub(body_area(popup_area(area)).height as usize)
+    }
+
+    /// Scrolls by `delta` lines of the log drawn over `area`. Reaching the
+    /// end follows new messages again.
+    fn scroll_by(&mut self, delta: isize, area: Rect) {
+        let max_scroll = self.max_scroll(area);
+        let scroll = self.scroll.unwrap_or(max_scroll).saturating_add_signed(delta).min(max_scroll);
+        self.scroll = if scroll == max_scroll { None } else { Some(scroll) };
+    }
+
+    /// Keys scroll, clear or close the log, which is drawn over `area`.
+    pub fn handle_key_event(&mut self, key_event: KeyEvent, area: Rect) {
+        let page = body_area(popup_area(area)).height.saturating_sub(1).max(1) as isize;
+        match key_event.code {
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_by(-1, area),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_by(1, area),
+            KeyCode::PageUp => self.scroll_by(-page, area),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll_by(page, area),
+            KeyCode::Home | KeyCode::Char('g') => self.scroll_by(isize::MIN, area),
+            KeyCode::End | KeyCode::Char('G') => self.scroll_by(isize::MAX, area),
+            KeyCode::Char('c') => {
+                self.log.clear();
+                self.scroll = None;
+            }
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') | KeyCode::Char('M') => self.close_log(),
+            _ => {}
+        }
+    }
+
+    /// The wheel scrolls and a click outside the log closes it.
+    pub fn handle_mouse_event(&mut self, mouse_event: MouseEvent, area: Rect) {
+        match mouse_event.kind {
+            MouseEventKind::ScrollUp => self.scroll_by(-WHEEL_LINES, area),
+            MouseEventKind::ScrollDown => self.scroll_by(WHEEL_LINES, area),
+            MouseEventKind::Down(MouseButton::Left)
+                if !popup_area(area).contains(Position::new(mouse_event.column, mouse_event.row)) => self.close_log(),
+            _ => {}
+        }
+    }
+}
+
+/// Where the log is drawn over `area`.
+fn popup_area(area: Rect) -> Rect {
+    let div_vert = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Fill(1), Constraint::Percentage(70), Constraint::Fill(1)])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![Constraint::Fill(1), Constraint::Max(100), Constraint::Fill(1)])
+        .split(div_vert[1])[1]
+}
+
+fn body_area(area: Rect) -> Rect {
+    area.inner(Margin::new(1, 1))
+}
+
+impl Widget for &Notifications {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let area = popup_area(area);
+        Clear.render(area, buf);
+        let block = Block::bordered()
+            .title(" Messages ".bold())
+            .title_alignment(Alignment::Center)
+            .title_bottom(Line::from(vec![
+                " [↑↓]".bold(),
+                " Scroll ".into(),
+                " [c]".bold(),
+                " Clear ".into(),
+                " [Esc]".bold(),
+                " Close ".into(),
+            ]).alignment(Alignment::Center))
+            .border_set(border::ROUNDED)
+            .style(theme::current().popup);
+        block.render(area, buf);
+
+        let body = body_area(area);
+        if self.log.is_empty() {
+            Line::from("No messages").style(theme::current().label).alignment(Alignment::Center).render(body, buf);
+            return;
+        }
+        let max_scroll = self.log.len().saturating_sub(body.height as usize);
+        let scroll = self.scroll.unwrap_or(max_scroll).min(max_scroll);
+        let lines: Vec<Line> = self.log
+            .iter()
+            .skip(scroll)
+            .take(body.height as usize)
+            .map(|notification| {
+                let mut line = notification.to_line();
+                line.spans.insert(0, Span::styled(format!("{:>4} ago  ", format_age(notification.time)), theme::current().label));
+                line
+            })
+            .collect();
+        let scrolling = max_scroll > 0;
+        let text = Rect { width: body.width.saturating_sub(scrolling as u16 * 2), ..body };
+        Paragraph::new(lines).render(text, buf);
+        if scrolling {
+            let mut state = ScrollbarState::new(max_scroll).position(scroll);
+            Scrollbar::new(ScrollbarOrientation::VerticalRight).render(body, buf, &mut state);
+        }
+    }
+}
@@ -1,73 +1,290 @@
-use std::{fs::File, io::{BufReader}};
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+use std::{fmt, fs::File, io::{BufReader, Read, Seek, SeekFrom}, path::Path, time::Duration};
+use rodio::{Decoder, OutputStreamHandle, Sink, Source};
+use crate::analysis::{Levels, Tap, TapSource};
+
+/// Extensions of the formats rodio decodes with its default features.
+pub const SUPPORTED_EXTENSIONS: &[&str] = &["ogg", "oga", "mp3", "wav", "flac"];
+
+pub fn is_supported_audio(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SUPPORTED_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Header details read from an audio file without decoding all of it.
+#[derive(Clone, Debug)]
+pub struct AudioInfo {
+    pub format: &'static str,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub duration: Option<Duration>,
+}
+
+impl fmt::Display for AudioInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, {} ch, {} Hz", self.format, self.channels, self.sample_rate)?;
+        if let Some(duration) = self.duration {
+            let secs = duration.as_secs();
+            write!(f, ", {}:{:02}", secs / 60, secs % 60)?;
+        }
+        Ok(())
+    }
+}
+
+/// Opens `path` and decodes its headers, returning a readable reason when the
+/// file is missing or not something rodio can play.
+pub fn probe(path: &str) -> Result<AudioInfo, String> {
+    let mut file = File::open(path).map_err(|e| format!("Cannot open {}: {}", path, e))?;
+    let mut head = Vec::new();
+    (&mut file).take(HEAD_LEN).read_to_end(&mut head).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    let format = sniff_format(&head);
+    let ogg_duration = match ogg_page(&head) {
+        Some(page) if format == "Ogg Vorbis" => ogg_duration(&mut file, page.serial),
+        _ => None,
+    };
+    file.seek(SeekFrom::Start(0)).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+
+    let decoder = Decoder::new(BufReader::new(file)).map_err(|e| format!("Cannot decode {}: {}", path, e))?;
+    let sample_rate = decoder.sample_rate();
+    Ok(AudioInfo {
+        format,
+        channels: decoder.channels(),
+        sample_rate,
+        duration: decoder.total_duration().or_else(|| {
+            ogg_duration.map(|samples| Duration::from_secs_f64(samples as f64 / sample_rate as f64))
+        }),
+    })
+}
+
+/// Bytes read to tell the format, enough for the first Ogg page header and
+/// the start of its packet.
+const HEAD_LEN: u64 = 512;
+
+fn sniff_format(head: &[u8]) -> &'static str {
+    match head {
+        [b'O', b'g', b'g', b'S', ..] => match ogg_page(head).and_then(|page| head.get(page.body..)) {
+            Some(packet) if packet.starts_with(b"\x01vorbis") => "Ogg Vorbis",
+            Some(packet) if packet.starts_with(b"OpusHead") => "Ogg Opus",
+            Some(packet) if packet.starts_with(b"\x7FFLAC") => "Ogg FLAC",
+            _ => "Ogg",
+        },
+        [b'f', b'L', b'a', b'C', ..] => "FLAC",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => "WAV",
+        [b'I', b'D', b'3', ..] => "MP3",
+        [0xFF, b, ..] if b & 0xE0 == 0xE0 => "MP3",
+        _ => "Unknown",
+    }
+}
+
+/// Fields of an Ogg page header.
+struct OggPage {
+    granule: u64,
+    serial: u32,
+    /// Offset of the first packet from the start of the page.
+    body: usize,
+    /// Length of the whole page, header included.
+    len: usize,
+}
+
+/// Reads the page header at the start of `data`, checking the capture
+/// pattern, the version and that only known flags are set.
+fn ogg_page(data: &[u8]) -> Option<OggPage> {
+    let header = data.get(..27)?;
+    if &header[..4] != b"OggS" || header[4] != 0 || header[5] & !0x07 != 0 {
+        return None;
+    }
+    let segments = data.get(27..27 + header[26] as usize)?;
+    let body = 27 + segments.len();
+    Some(OggPage {
+        granule: u64::from_le_bytes(header[6..14].try_into().ok()?),
+        serial: u32::from_le_bytes(header[14..18].try_into().ok()?),
+        body,
+        len: body + segments.iter().map(|&len| len as usize).sum::<usize>(),
+    })
+}
+
+/// Whether the checksum of the page at the start of `data` matches, so that
+/// `OggS` met inside a packet is not taken for a page.
+fn ogg_checksum_matches(data: &[u8], len: usize) -> bool {
+    data.get(..len).is_some_and(|page| page[22..26] == ogg_checksum(page).to_le_bytes())
+}
+
+/// CRC-32 of a page as Ogg computes it, with the checksum field counted as
+/// zeros.
+fn ogg_checksum(page: &[u8]) -> u32 {
+    let mut crc: u32 = 0;
+    for (i, &byte) in page.iter().enumerate() {
+        let byte = if (22..26).contains(&i) { 0 } else { byte };
+        crc ^= (byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 { (crc << 1) ^ 0x04C1_1DB7 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// The Vorbis decoder does not report a length, but the granule position of
+/// the last page of the stream is the total number of samples per channel.
+fn ogg_duration(file: &mut File, serial: u32) -> Option<u64> {
+    const TAIL: u64 = 64 * 1024;
+    let len = file.metadata().ok()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL))).ok()?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail).ok()?;
+    last_granule(&tail, serial)
+}
+
+/// Walks back from the end of `tail` to the last whole page of the stream
+/// `serial` that ends a packet. Pages where no packet ends have a granule
+/// position of -1.
+fn last_granule(tail: &[u8], serial: u32) -> Option<u64> {
+    tail.windows(4)
+        .enumerate()
+        .rev()
+        .filter(|(_, window)| *window == b"OggS")
+        .filter_map(|(start, _)| {
+            let page = ogg_page(&tail[start..])?;
+            ogg_checksum_matches(&tail[start..], page.len).then_some(page)
+        })
+        .find(|page| page.serial == serial && page.granule != u64::MAX)
+        .map(|page| page.granule)
+}
 
 pub struct Sound {
     path: String,
     volume: f32,
     sink: Option<Sink>,
+    muted: bool,
+    /// Master volume the sound was last set with.
+    mult: f32,
+    info: Result<AudioInfo, String>,
+    tap: Tap,
+    /// Without an audio device, the decoded file advanced by `advance` so the
+    /// levels still move, and whether it is playing.
+    offline: Option<Box<dyn Source<Item = f32> + Send>>,
+    playing: bool,
 }
 
 impl Sound {
     pub fn new(path: String, volume: f32, stream_handle: &OutputStreamHandle) -> Self {
-        let sink = Sink::try_new(&stream_handle).ok();
-        if let Some(ref sink) = sink {
-            if let Ok(file) = File::open(&path) {
-                let file = BufReader::new(file);
-                if let Ok(source) = Decoder::new(file) {
-                    sink.append(source.repeat_infinite());
-                    sink.pause();
-                }
-            }
+        let info = probe(&path);
+        let tap = Tap::new();
+        let sink = Sink::try_new(stream_handle).ok();
+        if let Some(ref sink) = sink
+            && info.is_ok()
+            && let Some(source) = decode(&path, &tap) {
+            sink.append(source);
+            sink.pause();
         }
-        Sound { path, volume, sink }
+        Sound { path, volume, sink, muted: false, mult: 1.0, info, tap, offline: None, playing: false }
     }
 
     pub fn new_no_audio(path: String, volume: f32) -> Self {
-        Sound { path, volume, sink: None }
+        let info = probe(&path);
+        let tap = Tap::new();
+        let offline = info.as_ref().ok().and_then(|_| decode(&path, &tap)).map(|source| Box::new(source) as Box<dyn Source<Item = f32> + Send>);
+        Sound { path, volume, sink: None, muted: false, mult: 1.0, info, tap, offline, playing: false }
+    }
+
+    /// Header details of the file, if it could be loaded.
+    pub fn get_info(&self) -> Option<&AudioInfo> {
+        self.info.as_ref().ok()
+    }
+
+    /// Why the file could not be loaded, if it could not.
+    pub fn get_error(&self) -> Option<&str> {
+        self.info.as_ref().err().map(String::as_str)
     }
 
     pub fn get_path(&self) -> &str {
         &self.path
     }
 
-    pub fn play(&self) {
+    pub fn play(&mut self) {
+        self.playing = true;
         if let Some(ref sink) = self.sink {
             sink.play();
         }
     }
 
-    pub fn pause(&self) {
+    pub fn pause(&mut self) {
+        self.playing = false;
         if let Some(ref sink) = self.sink {
             sink.pause();
         }
     }
 
+    /// Decodes what would have played over `elapsed` when there is no audio
+    /// device to pull the samples. Does nothing otherwise.
+    pub fn advance(&mut self, elapsed: Duration) {
+        if !self.playing {
+            return;
+        }
+        let Some(source) = &mut self.offline else { return };
+        let rate = source.sample_rate() as f32 * source.channels() as f32;
+        let samples = (elapsed.min(Duration::from_secs(1)).as_secs_f32() * rate) as usize;
+        source.by_ref().take(samples).for_each(drop);
+    }
+
+    /// The latest samples heard, scaled by the effective volume, or nothing
+    /// while paused.
+    pub fn get_window(&self) -> Option<Vec<f32>> {
+        if !self.is_playing() {
+            return None;
+        }
+        let gain = self.get_effective_volume();
+        Some(self.tap.window().into_iter().map(|sample| sample * gain).collect())
+    }
+
+    pub fn get_levels(&self) -> Levels {
+        self.get_window().map_or(Levels::default(), |window| Levels::measure(&window))
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.tap.get_sample_rate()
+    }
+
     pub fn switch_play_pause(&mut self) {
-        if let Some(ref sink) = self.sink {
-            if sink.is_paused() {
-                sink.play();
-            } else {
-                sink.pause();
-            }
+        if self.is_playing() {
+            self.pause();
+        } else {
+            self.play();
         }
     }
 
     pub fn set_volume(&mut self, volume: f32, mult: f32) {
         self.volume = volume.clamp(0.0, 1.0);
+        self.mult = mult;
         if let Some(ref sink) = self.sink {
-            sink.set_volume(self.volume * mult);
+            sink.set_volume(self.get_effective_volume());
         }
     }
 
+    /// Silences the sink without touching the volume, so unmuting restores it.
+    pub fn set_muted(&mut self, muted: bool, mult: f32) {
+        self.muted = muted;
+        self.set_volume(self.volume, mult);
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
     pub fn get_volume(&self) -> f32 {
         self.volume
     }
 
+    /// The level actually played: the volume times the master volume, or
+    /// nothing while muted.
+    pub fn get_effective_volume(&self) -> f32 {
+        if self.muted { 0.0 } else { self.volume * self.mult }
+    }
+
     pub fn is_playing(&self) -> bool {
         if let Some(ref sink) = self.sink {
             return !sink.is_paused();
         }
-        false
+        self.offline.is_some() && self.playing
     }
 }
 
@@ -77,6 +294,82 @@ impl Clone for Sound {
             path: self.path.clone(),
             volume: self.volume,
             sink: None,
+            muted: self.muted,
+            mult: self.mult,
+            info: self.info.clone(),
+            tap: Tap::new(),
+            offline: None,
+            playing: false,
         }
     }
-}
\ No newline at end of file
+}
+
+/// The file decoded and looped forever, feeding `tap`.
+fn decode(path: &str, tap: &Tap) -> Option<TapSource<impl Source<Item = f32> + Send + use<>>> {
+    let source = Decoder::new(BufReader::new(File::open(path).ok()?)).ok()?;
+    Some(TapSource::new(source.repeat_infinite().convert_samples(), tap.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An Ogg page of stream `serial` holding one packet, with its checksum.
+    fn page(flags: u8, granule: u64, serial: u32, packet: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS".to_vec();
+        page.extend([0, flags]);
+        page.extend(granule.to_le_bytes());
+        page.extend(serial.to_le_bytes());
+        page.extend([0; 8]);
+        page.extend([1, packet.len() as u8]);
+        page.extend(packet);
+        let checksum = ogg_checksum(&page);
+        page[22..26].copy_from_slice(&checksum.to_le_bytes());
+        page
+    }
+
+    #[test]
+    fn bundled_sounds_have_a_length() {
+        let info = probe("resources/sounds/rain.ogg").unwrap();
+        assert_eq!(info.format, "Ogg Vorbis");
+        assert!(info.duration.is_some_and(|duration| duration > Duration::from_secs(1)), "{:?}", info.duration);
+    }
+
+    #[test]
+    fn ogg_codecs_are_told_apart() {
+        assert_eq!(sniff_format(&page(0x02, 0, 1, b"\x01vorbis\x00\x00\x00\x00")), "Ogg Vorbis");
+        assert_eq!(sniff_format(&page(0x02, 0, 1, b"OpusHead\x01")), "Ogg Opus");
+        assert_eq!(sniff_format(&page(0x02, 0, 1, b"\x7FFLAC\x01\x00")), "Ogg FLAC");
+        assert_eq!(sniff_format(&page(0x02, 0, 1, b"\x80theora")), "Ogg");
+        assert_eq!(sniff_format(b"OggS\x01"), "Ogg");
+        assert_eq!(sniff_format(b"fLaC\x00\x00\x00\x22"), "FLAC");
+        assert_eq!(sniff_format(b"RIFF\x24\x00\x00\x00WAVEfmt "), "WAV");
+        assert_eq!(sniff_format(b"ID3\x04"), "MP3");
+        assert_eq!(sniff_format(b"hello"), "Unknown");
+    }
+
+    #[test]
+    fn the_last_page_of_the_stream_gives_the_length() {
+        let mut tail = page(0, 44_100, 7, b"audio");
+        tail.extend(page(0, 88_200, 9, b"other stream"));
+        tail.extend(page(0x04, u64::MAX, 7, b"no packet ends here"));
+        assert_eq!(last_granule(&tail, 7), Some(44_100));
+        assert_eq!(last_granule(&tail, 9), Some(88_200));
+        assert_eq!(last_granule(&tail, 8), None);
+    }
+
+    #[test]
+    fn broken_pages_are_not_trusted() {
+        let good = page(0, 1_000, 7, b"audio");
+        let mut bad_version = page(0, 2_000, 7, b"audio");
+        bad_version[4] = 1;
+        let mut bad_flags = page(0, 3_000, 7, b"audio");
+        bad_flags[5] = 0x80;
+        let mut bad_checksum = page(0, 4_000, 7, b"audio");
+        bad_checksum[30] ^= 0xFF;
+        let truncated = &page(0, 5_000, 7, b"audio")[..20];
+        let tail = [good, bad_version, bad_flags, bad_checksum, truncated.to_vec()].concat();
+        assert_eq!(last_granule(&tail, 7), Some(1_000));
+        assert_eq!(last_granule(b"OggS in the middle of a packet", 7), None);
+    }
+}
@@ -1,22 +1,56 @@
-use std::fs;
+use ratatui::{buffer::Buffer, crossterm::event::{KeyEvent, MouseButton, MouseEvent, MouseEventKind}, layout::{Alignment, Constraint, Direction, Layout, Position, Rect}, style::Stylize, symbols::border, text::Line, widgets::{Block, Widget}};
+use rodio::OutputStreamHandle;
+use crate::app::resolve_sound_path;
+use crate::components::{file_browser::{complete_path, FileBrowser}, input::Input, notifications::Notification, sound::probe};
+use crate::config::{ConfigDocument, SoundConfig};
+use crate::keymap::Action;
+use crate::theme;
 
-use ratatui::{buffer::Buffer, crossterm::event::{KeyCode, KeyEvent}, layout::{Alignment, Constraint, Direction, Layout, Rect}, style::{self, Color, Style, Stylize}, symbols::border, text::{Line, Text}, widgets::{Block, Paragraph, Widget}};
-use crate::{app::RESOURCES_PATH, components::input::Input};
-use crate::config::{Config, SoundConfig};
+/// Inputs before this index must be filled; the rest are optional.
+const REQUIRED_INPUTS: usize = 3;
+
+/// An entry changed through the popup, identified by the name and resolved
+/// path it had before the edit.
+pub struct SoundEdit {
+    pub name: String,
+    pub path: String,
+    pub sound: SoundConfig,
+}
+
+enum PopupMode {
+    Add,
+    /// `sound` is the entry as loaded, so fields the popup does not show are kept.
+    Edit { name: String, path: String, sound: Box<SoundConfig> },
+}
 
 pub struct SoundAddPopup {
     inputs: Vec<Input>,
     opened: bool,
+    mode: PopupMode,
+    edited: Option<SoundEdit>,
+    browser: FileBrowser,
+    error: Option<String>,
+    file_info: Option<String>,
+    /// Problems met since the app last took them.
+    notifications: Vec<Notification>,
 }
 
 impl SoundAddPopup {
-    pub fn new() -> Self {
+    pub fn new(stream_handle: Option<OutputStreamHandle>) -> Self {
         let name_input = Input::new(" Name ".to_string(), 50, true);
-        let file_input = Input::new(" File Path ".to_string(), 100, false);
+        let file_input = Input::new(" File Path ".to_string(), 255, false);
         let icon_input = Input::new(" Icon ".to_string(), 1, false);
+        let category_input = Input::new(" Category (optional) ".to_string(), 30, false);
+        let tags_input = Input::new(" Tags, comma separated (optional) ".to_string(), 100, false);
         SoundAddPopup {
-            inputs: vec![name_input, file_input, icon_input],
+            inputs: vec![name_input, file_input, icon_input, category_input, tags_input],
             opened: false,
+            mode: PopupMode::Add,
+            edited: None,
+            browser: FileBrowser::new(stream_handle),
+            error: None,
+            file_info: None,
+            notifications: vec![],
         }
     }
 
@@ -32,6 +66,20 @@ impl SoundAddPopup {
         self.inputs[2].get_text()
     }
 
+    pub fn get_category(&self) -> Option<&str> {
+        Some(self.inputs[3].get_text().trim()).filter(|category| !category.is_empty())
+    }
+
+    pub fn get_tags(&self) -> Vec<String> {
+        self.inputs[4]
+            .get_text()
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
     pub fn get_opened(&self) -> bool {
         self.opened
     }
@@ -42,6 +90,44 @@ impl SoundAddPopup {
             input.clear();
         }
         self.inputs[0].set_active(true); // Activate the first input
+        self.mode = PopupMode::Add;
+        self.browser.close();
+        self.error = None;
+        self.file_info = None;
+    }
+
+    /// Opens the popup prefilled with the `sounds.toml` entry of the given sound.
+    pub fn open_edit(&mut self, name: &str, path: &str) {
+        let sound = match ConfigDocument::load() {
+            Ok(document) => document.get_sound(name, path),
+            Err(e) => {
+                self.notifications.push(Notification::error(format!("Could not read the sounds file: {}", e)));
+                return;
+            }
+        };
+        let Some(sound) = sound else {
+            self.notifications.push(Notification::error(format!("Sound {} not found in the sounds file", name)));
+            return;
+        };
+        self.clear();
+        self.inputs[0].set_text(&sound.name);
+        self.inputs[1].set_text(&sound.file);
+        self.inputs[2].set_text(&sound.icon);
+        self.inputs[3].set_text(sound.category.as_deref().unwrap_or_default());
+        self.inputs[4].set_text(&sound.tags.join(", "));
+        self.mode = PopupMode::Edit { name: name.to_string(), path: path.to_string(), sound: Box::new(sound) };
+        self.probe_file_path();
+        self.set_opened(true);
+    }
+
+    /// Returns the messages gathered since the last call.
+    pub fn take_notifications(&mut self) -> Vec<Notification> {
+        std::mem::take(&mut self.notifications)
+    }
+
+    /// Returns the last entry edited through the popup, if it was not taken yet.
+    pub fn take_edit(&mut self) -> Option<SoundEdit> {
+        self.edited.take()
     }
 
     pub fn set_opened(&mut self, opened: bool) {
@@ -59,90 +145,195 @@ impl SoundAddPopup {
         }
     }
 
-    pub fn submit_instruction(&mut self) {
-        for input in &mut self.inputs {
-            if input.get_text().is_empty() {
-                input.switch_active();
-                return;
+    /// Completes the file path input. Returns false when there was nothing to
+    /// complete, so Tab can fall back to switching inputs.
+    fn complete_file_path(&mut self) -> bool {
+        if !self.inputs[1].get_active() {
+            return false;
+        }
+        match complete_path(self.get_file_path()) {
+            Some(completed) => {
+                self.inputs[1].set_text(&completed);
+                if !completed.ends_with('/') {
+                    self.probe_file_path();
+                }
+                true
             }
+            None => false,
         }
-        let sounds_file = fs::read_to_string(RESOURCES_PATH.to_string() + "sounds.toml");
-        if let Err(e) = sounds_file {
-            eprintln!("Error reading sounds file: {}", e);
-            return;
+    }
+
+    /// Shows the details of the chosen file, or why it cannot be used.
+    fn probe_file_path(&mut self) {
+        match probe(&resolve_sound_path(self.get_file_path())) {
+            Ok(info) => {
+                self.file_info = Some(info.to_string());
+                self.error = None;
+            }
+            Err(e) => {
+                self.file_info = None;
+                self.error = Some(e);
+            }
         }
-        let toml_file = sounds_file.unwrap();
-        let mut config: Config = toml::from_str(&toml_file).unwrap();
-        config.sound.push(SoundConfig {
-            name: self.get_name().to_string(),
-            file: self.get_file_path().to_string(),
-            icon: self.get_icon_path().to_string(),
-        });
-        let new_toml = toml::to_string(&config).unwrap();
-        if let Err(e) = fs::write(RESOURCES_PATH.to_string() + "sounds.toml", new_toml) {
-            eprintln!("Error writing to sounds file: {}", e);
-            return;
+    }
+
+    /// Index of the first required input left empty.
+    fn first_empty_required(&self) -> Option<usize> {
+        self.inputs[..REQUIRED_INPUTS].iter().position(|input| input.get_text().is_empty())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.first_empty_required().is_some() {
+            return Err("Name, file path and icon must be filled".to_string());
         }
+        probe(&resolve_sound_path(self.get_file_path())).map(|_| ())
     }
 
-    pub fn handle_key_event(&mut self, key_event: KeyEvent) {
-        match key_event.code {
-            KeyCode::Tab => {
-                self.cycle_active_input();
+    /// Saves the entry, returning whether the popup can be closed.
+    pub fn submit_instruction(&mut self) -> bool {
+        if let Err(message) = self.validate() {
+            if let Some(empty) = self.first_empty_required() {
+                for (i, input) in self.inputs.iter_mut().enumerate() {
+                    input.set_active(i == empty);
+                }
+            }
+            self.error = Some(message);
+            return false;
+        }
+        let mut document = match ConfigDocument::load() {
+            Ok(document) => document,
+            Err(e) => {
+                self.notifications.push(Notification::error(format!("Could not read the sounds file: {}", e)));
+                return true;
+            }
+        };
+        let mut sound = match &self.mode {
+            PopupMode::Add => SoundConfig::default(),
+            PopupMode::Edit { sound, .. } => sound.as_ref().clone(),
+        };
+        sound.name = self.get_name().to_string();
+        sound.file = self.get_file_path().to_string();
+        sound.icon = self.get_icon_path().to_string();
+        sound.category = self.get_category().map(str::to_string);
+        sound.tags = self.get_tags();
+        match &self.mode {
+            PopupMode::Add => document.push_sound(&sound),
+            PopupMode::Edit { name, path, .. } => {
+                if !document.update_sound(name, path, &sound) {
+                    self.notifications.push(Notification::error(format!("Sound {} not found in the sounds file", name)));
+                    return true;
+                }
             }
-            KeyCode::Enter => {
-                self.submit_instruction();
+        }
+        if let Err(e) = document.save() {
+            self.notifications.push(Notification::error(format!("Could not write the sounds file: {}", e)));
+            return true;
+        }
+        if let PopupMode::Edit { name, path, .. } = &self.mode {
+            self.edited = Some(SoundEdit { name: name.clone(), path: path.clone(), sound });
+        }
+        true
+    }
+
+    /// Whether the file browser has the focus, in which case it handles
+    /// every key itself.
+    pub fn is_browsing(&self) -> bool {
+        self.browser.get_opened()
+    }
+
+    pub fn handle_action(&mut self, action: Action) {
+        match action {
+            Action::ClosePopup => {
                 self.set_opened(false);
                 self.clear();
             }
-            _ => {
-                for input in &mut self.inputs {
-                    if input.get_active() {
-                        input.handle_key_event(key_event);
-                        break;
-                    }
-                }
+            Action::BrowseFiles => {
+                self.browser.open(self.inputs[1].get_text());
+            }
+            Action::NextInput if !self.complete_file_path() => self.cycle_active_input(),
+            Action::SubmitPopup if self.submit_instruction() => {
+                self.set_opened(false);
+                self.clear();
+            }
+            _ => {}
+        }
+    }
+
+    /// Clicking outside the popup, drawn over `area`, closes it.
+    pub fn handle_mouse_event(&mut self, mouse_event: MouseEvent, area: Rect) {
+        if mouse_event.kind == MouseEventKind::Down(MouseButton::Left)
+            && !popup_area(area).contains(Position::new(mouse_event.column, mouse_event.row)) {
+            self.handle_action(Action::ClosePopup);
+        }
+    }
+
+    /// Keys without an action go to the file browser or the active input.
+    pub fn handle_key_event(&mut self, key_event: KeyEvent) {
+        if self.browser.get_opened() {
+            if let Some(path) = self.browser.handle_key_event(key_event) {
+                self.inputs[1].set_text(&path);
+                self.probe_file_path();
             }
-        } 
+            return;
+        }
+        for input in &mut self.inputs {
+            if input.get_active() {
+                input.handle_key_event(key_event);
+                break;
+            }
+        }
     }
 }
 
+/// Where the popup is drawn over `area`.
+fn popup_area(area: Rect) -> Rect {
+    let div_vert = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Fill(1), Constraint::Min(21), Constraint::Fill(1)])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![Constraint::Fill(1), Constraint::Min(30), Constraint::Fill(1)])
+        .split(div_vert[1])[1]
+}
+
 impl Widget for &SoundAddPopup {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        
-        let div_vert = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Fill(1), Constraint::Min(14), Constraint::Fill(1)])
-            .split(area);
-        let div_vert_hor = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints(vec![Constraint::Fill(1), Constraint::Min(30), Constraint::Fill(1)])
-            .split(div_vert[1]);
+        let area = popup_area(area);
 
+        let title = match self.mode {
+            PopupMode::Add => " Add Sound ",
+            PopupMode::Edit { .. } => " Edit Sound ",
+        };
         let block = Block::bordered()
-            .title(" Add Sound ".bold())
+            .title(title.bold())
             .title_alignment(Alignment::Center)
             .border_set(border::EMPTY)
-            .style(Style::default().bg(Color::Black));
-        block.render(div_vert_hor[1], buf);
+            .style(theme::current().popup);
+        block.render(area, buf);
         let mut constraints = vec![Constraint::Length(3); self.inputs.len()];
         constraints.insert(0, Constraint::Max(2));
         constraints.push(Constraint::Length(1));
+        constraints.push(Constraint::Length(1));
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .margin(1)
             .constraints(constraints)
-            .split(div_vert_hor[1]);
+            .split(area);
 
         let instructions_chunks = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints(vec![Constraint::Fill(1); 2])
+            .constraints(vec![Constraint::Fill(1); 3])
             .split(chunks[0]);
 
         let quit_insruction = Line::from(vec![
             "[Esc]".bold(),
             " Close".into()
         ]).alignment(Alignment::Left);
+        let browse_instruction = Line::from(vec![
+            "[Ctrl-o]".bold(),
+            " Browse".into()
+        ]).alignment(Alignment::Center);
         let switch_instruction = Line::from(vec![
             "[Tab]".bold(),
             " Switch".into()
@@ -153,12 +344,29 @@ impl Widget for &SoundAddPopup {
         ]).alignment(Alignment::Center);
 
         quit_insruction.render(instructions_chunks[0], buf);
-        switch_instruction.render(instructions_chunks[1], buf);
+        browse_instruction.render(instructions_chunks[1], buf);
+        switch_instruction.render(instructions_chunks[2], buf);
         submit_instruction.render(chunks[chunks.len()-1], buf);
 
+        if let Some(error) = &self.error {
+            Line::from(error.as_str())
+                .style(theme::current().error)
+                .alignment(Alignment::Center)
+                .render(chunks[chunks.len()-2], buf);
+        } else if let Some(file_info) = &self.file_info {
+            Line::from(file_info.as_str())
+                .style(theme::current().label)
+                .alignment(Alignment::Center)
+                .render(chunks[chunks.len()-2], buf);
+        }
+
         for (i, input) in self.inputs.iter().enumerate() {
             let input_area = chunks[i + 1];
             input.render(input_area, buf);
         }
+
+        if self.browser.get_opened() {
+            self.browser.render(area, buf);
+        }
     }
 }
\ No newline at end of file
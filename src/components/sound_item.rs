@@ -1,43 +1,76 @@
-use std::io;
+use std::{io, rc::Rc, time::Duration};
 
 use ratatui::{
-    buffer::Buffer, crossterm::event::KeyCode, layout::{Alignment, Constraint, Layout, Rect}, style::{Color, Style, Stylize}, text::Text, widgets::{Block, Paragraph, Widget}
+    buffer::Buffer, layout::{Alignment, Constraint, Layout, Margin, Rect}, style::{Color, Style, Stylize}, symbols::border, text::{Line, Span}, widgets::{Block, Paragraph, Widget}
 };
 use rodio::OutputStreamHandle;
-use crate::components::sound::Sound;
+use crate::{analysis::Levels, components::{level_meter::{LevelMeter, METER_WIDTH}, sound::{AudioInfo, Sound}, volume_gauge::VolumeGauge}, config::SoundConfig, keymap::Action, theme::{self, Theme}};
 
 pub struct SoundItem {
     id: u32,
     name: String,
     icon: String,
+    category: Option<String>,
+    tags: Vec<String>,
+    favorite: bool,
+    author: Option<String>,
+    license: Option<String>,
+    source_url: Option<String>,
+    /// Char indexes of the name matched by the current search.
+    highlight: Vec<usize>,
     selected: bool,
     active: bool,
+    /// Whether the level meter is drawn.
+    meter: bool,
     sound: Sound
 }
 
 impl SoundItem {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(id: u32, name: String, path: String, volume: f32, icon: String, selected: bool, active: bool, stream_handle: Option<&OutputStreamHandle>) -> Self {
-        let sound = match stream_handle {
-            Some(handle) => Sound::new(path, volume, handle),
-            None => {
-                // Create a sound that will have None sink (audio disabled)
-                Sound::new_no_audio(path, volume)
-            }
-        };
+        let sound = load_sound(path, volume, stream_handle);
         SoundItem {
             id,
             name,
             icon,
+            category: None,
+            tags: vec![],
+            favorite: false,
+            author: None,
+            license: None,
+            source_url: None,
+            highlight: vec![],
             selected,
             active,
+            meter: false,
             sound
         }
     }
 
+    /// Applies an edit from the popup. A changed file is reloaded, keeping the
+    /// volume and whether the sound was playing.
+    pub fn update(&mut self, name: String, path: String, icon: String, stream_handle: Option<&OutputStreamHandle>, mult: f32) {
+        self.name = name;
+        self.icon = icon;
+        if path != self.sound.get_path() {
+            let volume = self.sound.get_volume();
+            let playing = self.sound.is_playing();
+            self.sound = load_sound(path, volume, stream_handle);
+            self.sound.set_volume(volume, mult);
+            if playing {
+                self.sound.play();
+            }
+        }
+    }
+
     pub fn toggle_selection(&mut self) {
         self.selected = !self.selected;
     }
 
+    pub fn set_selected(&mut self, selected: bool) {
+        self.selected = selected;
+    }
+
     pub fn toggle_active(&mut self) {
         self.active = !self.active;
     }
@@ -54,6 +87,86 @@ impl SoundItem {
         &self.icon
     }
 
+    pub fn get_category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+
+    pub fn get_tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn is_favorite(&self) -> bool {
+        self.favorite
+    }
+
+    pub fn toggle_favorite(&mut self) {
+        self.favorite = !self.favorite;
+    }
+
+    pub fn get_author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    pub fn get_license(&self) -> Option<&str> {
+        self.license.as_deref()
+    }
+
+    pub fn get_source_url(&self) -> Option<&str> {
+        self.source_url.as_deref()
+    }
+
+    pub fn get_info(&self) -> Option<&AudioInfo> {
+        self.sound.get_info()
+    }
+
+    pub fn get_error(&self) -> Option<&str> {
+        self.sound.get_error()
+    }
+
+    /// Copies the fields of the `sounds.toml` entry that do not affect
+    /// playback: grouping, tags and credits.
+    pub fn set_details(&mut self, sound: &SoundConfig) {
+        self.category = sound.category.clone();
+        self.tags = sound.tags.clone();
+        self.favorite = sound.favorite;
+        self.author = sound.author.clone();
+        self.license = sound.license.clone();
+        self.source_url = sound.source_url.clone();
+    }
+
+    pub fn set_meter(&mut self, meter: bool) {
+        self.meter = meter;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.sound.is_playing()
+    }
+
+    pub fn get_levels(&self) -> Levels {
+        self.sound.get_levels()
+    }
+
+    /// The latest samples heard, while the sound plays.
+    pub fn get_window(&self) -> Option<Vec<f32>> {
+        self.sound.get_window()
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sound.get_sample_rate()
+    }
+
+    pub fn advance(&mut self, elapsed: Duration) {
+        self.sound.advance(elapsed);
+    }
+
+    pub fn set_highlight(&mut self, highlight: Vec<usize>) {
+        self.highlight = highlight;
+    }
+
+    pub fn set_muted(&mut self, muted: bool, mult: f32) {
+        self.sound.set_muted(muted, mult);
+    }
+
     pub fn get_path(&self) -> &str {
         self.sound.get_path()
     }
@@ -66,6 +179,25 @@ impl SoundItem {
         self.id
     }
     
+    pub fn set_volume(&mut self, volume: f32, mult: f32) {
+        self.sound.set_volume(volume, mult);
+    }
+
+    pub fn get_volume(&self) -> f32 {
+        self.sound.get_volume()
+    }
+
+    /// Activates or deactivates the sound the same way Space does, so playback
+    /// only starts while the general state is playing.
+    pub fn set_active(&mut self, active: bool, general_play_status: bool) {
+        if self.active != active {
+            if general_play_status {
+                self.sound.switch_play_pause();
+            }
+            self.toggle_active();
+        }
+    }
+
     pub fn change_volume(&mut self, delta: f32, mult: f32) {
         self.sound.set_volume(self.sound.get_volume() + delta, mult);
     }
@@ -74,12 +206,92 @@ impl SoundItem {
         self.sound.switch_play_pause();
     }
 
-    pub fn handle_key_event(&mut self, key: KeyCode, general_play_status: bool, mult: f32) -> io::Result<()> {
+    /// Name, level meter and volume columns of a row drawn in `area`.
+    fn columns(area: Rect, meter: bool) -> Rc<[Rect]> {
+        Layout::default()
+            .direction(ratatui::layout::Direction::Horizontal)
+            .constraints([
+                Constraint::Min(1),
+                Constraint::Length(if meter { METER_WIDTH + 1 } else { 0 }),
+                // A third of the row
+                Constraint::Length((area.width / 3).clamp(20, 48))
+            ])
+            .horizontal_margin(1)
+            .split(area)
+    }
+
+    /// Where the volume is drawn in a row drawn in `area`.
+    pub fn volume_area(area: Rect) -> Rect {
+        SoundItem::columns(area, false)[2]
+    }
+
+    /// Where the volume gauge is drawn in a card drawn in `area`.
+    pub fn card_volume_area(area: Rect) -> Rect {
+        let inner = area.inner(Margin::new(1, 1));
+        Rect::new(inner.x, inner.bottom().saturating_sub(1), inner.width, inner.height.min(1))
+    }
+
+    /// Style of the text of the sound: muted sounds and sounds that failed
+    /// to load stand out.
+    fn text_style(&self, theme: &Theme) -> Style {
+        let mut style = theme.inactive;
+        if self.sound.is_muted() {
+            style = style.patch(theme.muted);
+        }
+        if self.sound.get_error().is_some() {
+            style = style.patch(theme.error);
+        }
+        style
+    }
+
+    fn volume_gauge(&self, style: Style) -> VolumeGauge {
+        VolumeGauge::new(self.sound.get_volume(), self.sound.get_effective_volume(), style)
+    }
+
+    /// Draws the sound as a card of the grid: the icon, the name and a
+    /// volume gauge, with a thick frame when selected and filled when active.
+    pub fn render_card(&self, area: Rect, buf: &mut Buffer) {
+        let theme = theme::current();
+        let mut block = Block::bordered().border_set(border::ROUNDED).style(theme.inactive).border_style(theme.border);
+        if self.is_active() {
+            block = block.style(theme.inactive.patch(theme.active));
+        }
+        if self.selected {
+            let frame = Style::default().fg(theme.selected.bg.unwrap_or(Color::Reset)).add_modifier(theme.selected.add_modifier);
+            block = block.border_set(border::THICK).border_style(frame);
+        }
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let mut name_style = self.text_style(theme);
         if self.selected {
-            match key {
-                KeyCode::Left => { self.change_volume(-0.05, mult); },
-                KeyCode::Right => { self.change_volume(0.05, mult); },
-                KeyCode::Char(' ') => { 
+            name_style = name_style.add_modifier(theme.selected.add_modifier);
+        }
+        let mut name_spans = vec![];
+        if self.favorite {
+            name_spans.push(Span::styled("★ ", theme.highlight));
+        }
+        name_spans.push(Span::raw(self.name.as_str()));
+        let icon = if self.sound.get_error().is_some() { "⚠" } else { self.icon.as_str() };
+
+        let chunks = Layout::default()
+            .direction(ratatui::layout::Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Length(1), Constraint::Fill(1), Constraint::Length(1)])
+            .split(inner);
+        Paragraph::new(format!(" {} ", icon)).style(name_style).alignment(Alignment::Center).render(chunks[0], buf);
+        Paragraph::new(Line::from(name_spans)).style(name_style).alignment(Alignment::Center).render(chunks[1], buf);
+        if self.meter {
+            LevelMeter::new(self.get_levels(), name_style).render(chunks[2].inner(Margin::new(1, 0)), buf);
+        }
+        self.volume_gauge(name_style).render(SoundItem::card_volume_area(area), buf);
+    }
+
+    pub fn handle_action(&mut self, action: Action, general_play_status: bool, mult: f32) -> io::Result<()> {
+        if self.selected {
+            match action {
+                Action::VolumeDown => { self.change_volume(-0.05, mult); },
+                Action::VolumeUp => { self.change_volume(0.05, mult); },
+                Action::ToggleSound => { 
                     if general_play_status {
                         self.sound.switch_play_pause();
                     }
@@ -91,55 +303,61 @@ impl SoundItem {
     }
 }
 
+fn load_sound(path: String, volume: f32, stream_handle: Option<&OutputStreamHandle>) -> Sound {
+    match stream_handle {
+        Some(handle) => Sound::new(path, volume, handle),
+        None => {
+            // Create a sound that will have None sink (audio disabled)
+            Sound::new_no_audio(path, volume)
+        }
+    }
+}
+
 impl Widget for &SoundItem {
     fn render(self, area: Rect, buf: &mut Buffer) {
 
-        let item_block = Block::default()
-            .style({
-                if self.selected {
-                    Style::default().bg(Color::Blue)
-                } else {
-                    if self.is_active() {
-                        Style::default().bg(Color::Green)
-                    } else {
-                        Style::default()
-                    }
-                }
-
-            });
+        let theme = theme::current();
+        let mut row_style = theme.inactive;
+        if self.is_active() {
+            row_style = row_style.patch(theme.active);
+        }
+        if self.selected {
+            row_style = row_style.patch(theme.selected);
+        }
+        let item_block = Block::default().style(row_style);
         item_block.render(area, buf);
-        // Crear el layout horizontal para dividir el área en dos columnas
-        let chunks = Layout::default()
-            .direction(ratatui::layout::Direction::Horizontal)
-            .constraints([
-                Constraint::Min(1),    // Nombre - toma el espacio restante
-                Constraint::Length(15) // Volumen - ancho fijo de 15 caracteres
-            ])
-            .horizontal_margin(1)
-            .split(area);
-
-        // Crear el texto del nombre (lado izquierdo)
-        let name_style = if self.selected {
-            Style::default().fg(Color::White).bold()
-        } else {
-            Style::default().fg(Color::White)
-        };
+        let chunks = SoundItem::columns(area, self.meter);
+
+        let mut name_style = row_style;
+        if self.sound.is_muted() {
+            name_style = name_style.patch(theme.muted);
+        }
+        let mut name_spans = vec![Span::raw(format!("{} ", self.icon))];
+        if self.favorite {
+            name_spans.push(Span::styled("★ ", theme.highlight));
+        }
+        for (i, c) in self.name.chars().enumerate() {
+            if self.highlight.contains(&i) {
+                name_spans.push(Span::styled(c.to_string(), theme.highlight.bold().underlined()));
+            } else {
+                name_spans.push(Span::raw(c.to_string()));
+            }
+        }
+        if let Some(error) = self.sound.get_error() {
+            name_style = name_style.patch(theme.error);
+            name_spans.push(Span::raw(format!("  ⚠ {}", error)));
+        }
         
-        let mut name_paragraph = Paragraph::new(Text::from(format!("{} {}", self.icon, self.name)))
+        let name_paragraph = Paragraph::new(Line::from(name_spans))
             .style(name_style)
             .alignment(Alignment::Left);
 
-        // Crear el texto del volumen (lado derecho)
-        let volume_percentage = format!("{:.0}%", self.sound.get_volume() * 100.0);
-        let volume_bars = "|".repeat((self.sound.get_volume() * 10.0).round() as usize).fg(Color::White);
-        let volume_text = format!("{} {}", volume_bars, volume_percentage);
-        let volume_paragraph = Paragraph::new(Text::from(volume_text))
-            .style(name_style)
-            .alignment(Alignment::Right);
-        
-        // Renderizar ambos componentes
         name_paragraph.render(chunks[0], buf);
-        volume_paragraph.render(chunks[1], buf);
+        if self.meter {
+            let meter_area = Rect { width: METER_WIDTH, ..chunks[1] };
+            LevelMeter::new(self.get_levels(), name_style).render(meter_area, buf);
+        }
+        self.volume_gauge(name_style).render(chunks[2], buf);
     }
 }
 
@@ -149,8 +367,16 @@ impl Clone for SoundItem {
             id: self.id,
             name: self.name.clone(),
             icon: self.icon.clone(),
+            category: self.category.clone(),
+            tags: self.tags.clone(),
+            favorite: self.favorite,
+            author: self.author.clone(),
+            license: self.license.clone(),
+            source_url: self.source_url.clone(),
+            highlight: self.highlight.clone(),
             selected: self.selected,
             active: self.active,
+            meter: self.meter,
             sound: self.sound.clone()
         }
     }
@@ -1,24 +1,180 @@
-use ratatui::{buffer::Buffer, crossterm::event::KeyCode, layout::{Constraint, Layout, Rect}, style::Stylize, symbols::border, text::{Line, Text}, widgets::{Block, Widget}};
-use crate::{app::RESOURCES_PATH, components::sound_item::SoundItem, config::Config};
+use std::{collections::HashSet, time::Duration};
+
+use ratatui::{buffer::Buffer, crossterm::event::{KeyCode, MouseButton, MouseEvent, MouseEventKind}, layout::{Alignment, Constraint, Layout, Margin, Position, Rect}, style::Stylize, symbols::border, text::Line, widgets::{Block, Paragraph, Widget}};
+use rodio::OutputStreamHandle;
+use serde::{Deserialize, Serialize};
+use crate::{app::{resolve_sound_path, DEFAULT_VOLUME}, keymap::Action, fuzzy::fuzzy_match, theme, components::{notifications::Notification, sound_add_popup::SoundEdit, sound_item::SoundItem, volume_gauge::VolumeGauge}, config::{ConfigDocument, PresetConfig, SoundConfig}};
+
+/// Categories in the order upstream Blanket shows them. Other categories
+/// follow alphabetically, and sounds without one go last.
+pub const CATEGORIES: &[&str] = &["Nature", "Travel", "Interiors", "Noise"];
+pub const UNCATEGORIZED: &str = "Other";
+
+/// Orders `o` cycles through. Sorting is stable, so sounds that compare
+/// equal keep their relative order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortOrder {
+    Name,
+    Category,
+    ActiveFirst,
+}
+
+impl SortOrder {
+    pub fn next(self) -> Self {
+        match self {
+            SortOrder::Name => SortOrder::Category,
+            SortOrder::Category => SortOrder::ActiveFirst,
+            SortOrder::ActiveFirst => SortOrder::Name,
+        }
+    }
+}
+
+impl std::fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SortOrder::Name => "name",
+            SortOrder::Category => "category",
+            SortOrder::ActiveFirst => "active first",
+        })
+    }
+}
+
+/// Volume and playback state of one sound, as captured for undo.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MixerEntry {
+    pub name: String,
+    pub path: String,
+    pub volume: f32,
+    pub active: bool,
+}
+
+/// How the sounds are laid out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum View {
+    /// One line per sound.
+    #[default]
+    List,
+    /// Cards flowing across the width, like upstream Blanket.
+    Grid,
+}
+
+impl View {
+    pub fn next(self) -> Self {
+        match self {
+            View::List => View::Grid,
+            View::Grid => View::List,
+        }
+    }
+}
+
+impl std::fmt::Display for View {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            View::List => "list",
+            View::Grid => "grid",
+        })
+    }
+}
+
+/// Size of a card in the grid. Cards grow to share the width evenly.
+const CARD_WIDTH: u16 = 22;
+const CARD_HEIGHT: u16 = 6;
+
+/// A line of the grid: a category header, or the cards of up to a row's
+/// worth of sounds.
+enum GridLine {
+    Header(String),
+    Cards(Vec<usize>),
+}
+
+impl GridLine {
+    fn height(&self) -> u16 {
+        match self {
+            GridLine::Header(_) => 1,
+            GridLine::Cards(_) => CARD_HEIGHT,
+        }
+    }
+
+    fn rows(&self) -> Vec<Row> {
+        match self {
+            GridLine::Header(group) => vec![Row::Header(group.clone())],
+            GridLine::Cards(cards) => cards.iter().map(|index| Row::Sound(*index)).collect(),
+        }
+    }
+}
+
+/// A line of the list: either a category header or a sound, by its index in
+/// `sounds_list`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Row {
+    Header(String),
+    Sound(usize),
+}
 
 pub struct SoundsBlock {
     sounds_list: Vec<SoundItem>,
     lower_bound: usize,
     upper_bound: usize,
-    volume_mult: f32
+    page_size: usize,
+    volume_mult: f32,
+    selected_header: Option<String>,
+    collapsed: HashSet<String>,
+    muted_groups: HashSet<String>,
+    solo_group: Option<String>,
+    /// Sound soloed with `:solo`, silencing every other sound.
+    solo_sound: Option<String>,
+    /// Query of the `/` search. While set, only matching sounds are shown.
+    search: Option<String>,
+    /// Whether keys are being typed into the search query.
+    search_typing: bool,
+    favorites_only: bool,
+    /// Sound whose volume is being dragged with the mouse.
+    dragging: Option<usize>,
+    view: View,
+    /// Cards per line of the grid, from the width of the block.
+    columns: usize,
+    /// Whether rows and cards show the level of their sound.
+    meters: bool,
+    /// Problems met since the app last took them.
+    notifications: Vec<Notification>,
+    /// Whether `sounds.toml` was written since the app last asked.
+    library_changed: bool,
 }
 
 impl SoundsBlock {
     pub fn new(sounds: Vec<SoundItem>) -> Self {
-        SoundsBlock { sounds_list: sounds, lower_bound: 0, upper_bound: 4, volume_mult: 1.0 }
+        SoundsBlock { sounds_list: sounds, ..SoundsBlock::default() }
     }
 
     pub fn default() -> Self {
-        SoundsBlock { sounds_list: vec![], lower_bound: 0, upper_bound: 8, volume_mult: 1.0 }
+        SoundsBlock {
+            sounds_list: vec![],
+            lower_bound: 0,
+            upper_bound: 8,
+            page_size: 9,
+            volume_mult: 1.0,
+            selected_header: None,
+            collapsed: HashSet::new(),
+            muted_groups: HashSet::new(),
+            solo_group: None,
+            solo_sound: None,
+            search: None,
+            search_typing: false,
+            favorites_only: false,
+            dragging: None,
+            view: View::List,
+            columns: 1,
+            meters: false,
+            notifications: vec![],
+            library_changed: false,
+        }
     }
 
-    pub fn add_sound(&mut self, sound: SoundItem) {
+    pub fn add_sound(&mut self, mut sound: SoundItem) {
+        sound.set_meter(self.meters);
         self.sounds_list.push(sound);
+        self.apply_group_audibility();
     }
 
     pub fn get_sounds(&self) -> &Vec<SoundItem> {
@@ -29,6 +185,120 @@ impl SoundsBlock {
         self.sounds_list = sounds;
     }
 
+    /// Makes the list match `library`, in its order. Sounds already in the
+    /// list keep their volume and playback state, new ones start inactive and
+    /// sounds no longer in the library are dropped, which stops them.
+    pub fn sync_with(&mut self, library: &[SoundConfig], stream_handle: Option<&OutputStreamHandle>) {
+        let mult = self.volume_mult;
+        let mut old_sounds = std::mem::take(&mut self.sounds_list);
+        for (i, sound) in library.iter().enumerate() {
+            let path = resolve_sound_path(&sound.file);
+            let mut item = match old_sounds.iter().position(|s| s.get_name() == sound.name && s.get_path() == path) {
+                Some(index) => old_sounds.remove(index),
+                None => SoundItem::new(i as u32, sound.name.clone(), path.clone(), DEFAULT_VOLUME, sound.icon.clone(), false, false, stream_handle),
+            };
+            item.update(sound.name.clone(), path, sound.icon.clone(), stream_handle, mult);
+            item.set_details(sound);
+            item.set_meter(self.meters);
+            self.sounds_list.push(item);
+        }
+        let rows = self.rows();
+        if self.selected_row(&rows).is_none() {
+            self.selected_header = None;
+            let first = rows.iter().find(|row| matches!(row, Row::Sound(_))).or(rows.first()).cloned();
+            if let Some(row) = first {
+                self.select_row(&row);
+            }
+        }
+        self.apply_group_audibility();
+        self.apply_filter();
+    }
+
+    pub fn mixer_state(&self) -> Vec<MixerEntry> {
+        self.sounds_list
+            .iter()
+            .map(|sound| MixerEntry {
+                name: sound.get_name().to_string(),
+                path: sound.get_path().to_string(),
+                volume: sound.get_volume(),
+                active: sound.is_active(),
+            })
+            .collect()
+    }
+
+    /// Puts back the master volume and the state of every sound in `mixer`.
+    pub fn restore_mixer(&mut self, mixer: &[MixerEntry], master_volume: f32, general_play_status: bool) {
+        self.volume_mult = master_volume;
+        for sound in &mut self.sounds_list {
+            match mixer.iter().find(|entry| entry.name == sound.get_name() && entry.path == sound.get_path()) {
+                Some(entry) => {
+                    sound.set_volume(entry.volume, master_volume);
+                    sound.set_active(entry.active, general_play_status);
+                }
+                None => sound.change_volume(0.0, master_volume),
+            }
+        }
+        self.apply_group_audibility();
+    }
+
+    /// Returns the messages gathered since the last call.
+    pub fn take_notifications(&mut self) -> Vec<Notification> {
+        std::mem::take(&mut self.notifications)
+    }
+
+    /// Returns whether `sounds.toml` was written since the last call.
+    pub fn take_library_changed(&mut self) -> bool {
+        std::mem::take(&mut self.library_changed)
+    }
+
+    pub fn set_meters(&mut self, meters: bool) {
+        self.meters = meters;
+        for sound in &mut self.sounds_list {
+            sound.set_meter(meters);
+        }
+    }
+
+    /// Whether any sound is playing, so levels need redrawing.
+    pub fn is_playing(&self) -> bool {
+        self.sounds_list.iter().any(SoundItem::is_playing)
+    }
+
+    /// Moves the sounds decoded without an audio device forward in time.
+    pub fn advance(&mut self, elapsed: Duration) {
+        for sound in &mut self.sounds_list {
+            sound.advance(elapsed);
+        }
+    }
+
+    /// The latest samples of every playing sound mixed together, with their
+    /// sample rate, or `None` when nothing plays.
+    pub fn master_window(&self) -> Option<(Vec<f32>, u32)> {
+        let mut playing = self.sounds_list.iter().filter_map(|sound| Some((sound.get_window()?, sound.get_sample_rate())));
+        let (mut mix, sample_rate) = playing.next()?;
+        for (window, _) in playing {
+            for (sample, other) in mix.iter_mut().zip(window) {
+                *sample += other;
+            }
+        }
+        Some((mix, sample_rate))
+    }
+
+    pub fn get_selected_sound(&self) -> Option<&SoundItem> {
+        self.sounds_list.iter().find(|item| item.is_selected())
+    }
+
+    /// Applies an edit made in the popup to the matching sound in the list.
+    pub fn update_sound(&mut self, edit: SoundEdit, stream_handle: Option<&OutputStreamHandle>) {
+        let mult = self.volume_mult;
+        if let Some(sound_item) = self.sounds_list.iter_mut().find(|s| s.get_name() == edit.name && s.get_path() == edit.path) {
+            let path = resolve_sound_path(&edit.sound.file);
+            sound_item.set_details(&edit.sound);
+            sound_item.update(edit.sound.name, path, edit.sound.icon, stream_handle, mult);
+        }
+        self.apply_group_audibility();
+        self.scroll_to_selection();
+    }
+
     fn get_selected_sound_mut(&mut self) -> Option<(&mut SoundItem, usize)> {
         if let Some(index_of_item) = self.sounds_list.iter().position(|item| item.is_selected()) {
             return Some((&mut self.sounds_list[index_of_item], index_of_item));
@@ -36,35 +306,350 @@ impl SoundsBlock {
         None
     }
 
-    fn select_previous_sound(&mut self) -> usize {
-        for (i, sound) in self.sounds_list.iter_mut().enumerate() {
-            if sound.is_selected() {
-                sound.toggle_selection();
-                let previous_index = if i == 0 { 0 } else { i - 1 };
-                self.sounds_list[previous_index].toggle_selection();
-                return previous_index;
+    fn group_of(sound: &SoundItem) -> &str {
+        sound.get_category().unwrap_or(UNCATEGORIZED)
+    }
+
+    /// Sound indexes grouped by category, in display order.
+    pub fn groups(&self) -> Vec<(String, Vec<usize>)> {
+        let mut groups: Vec<(String, Vec<usize>)> = vec![];
+        for (i, sound) in self.sounds_list.iter().enumerate() {
+            let group = Self::group_of(sound);
+            match groups.iter_mut().find(|(name, _)| name == group) {
+                Some((_, members)) => members.push(i),
+                None => groups.push((group.to_string(), vec![i])),
+            }
+        }
+        let rank = |name: &str| match CATEGORIES.iter().position(|c| c.eq_ignore_ascii_case(name)) {
+            Some(position) => (0, position, String::new()),
+            None if name == UNCATEGORIZED => (2, 0, String::new()),
+            None => (1, 0, name.to_lowercase()),
+        };
+        groups.sort_by_key(|(name, _)| rank(name));
+        groups
+    }
+
+    /// Whether the sound at `index` passes the search and the favorites view.
+    /// Queries starting with `#` match tags by prefix, others match the name,
+    /// category or tags fuzzily.
+    fn is_visible(&self, index: usize) -> bool {
+        let sound = &self.sounds_list[index];
+        if self.favorites_only && !sound.is_favorite() {
+            return false;
+        }
+        let Some(query) = &self.search else {
+            return true;
+        };
+        if let Some(tag) = query.strip_prefix('#') {
+            let tag = tag.to_lowercase();
+            return sound.get_tags().iter().any(|t| t.to_lowercase().starts_with(&tag));
+        }
+        fuzzy_match(query, sound.get_name()).is_some()
+            || sound.get_category().is_some_and(|category| fuzzy_match(query, category).is_some())
+            || sound.get_tags().iter().any(|t| fuzzy_match(query, t).is_some())
+    }
+
+    fn has_filter(&self) -> bool {
+        self.search.is_some() || self.favorites_only
+    }
+
+    /// Lines currently shown. The list stays flat until a sound has a category.
+    /// While searching, groups without matches are hidden and the others are
+    /// shown expanded.
+    pub fn rows(&self) -> Vec<Row> {
+        if self.sounds_list.iter().all(|sound| sound.get_category().is_none()) {
+            return (0..self.sounds_list.len()).filter(|&i| self.is_visible(i)).map(Row::Sound).collect();
+        }
+        let mut rows = vec![];
+        for (group, members) in self.groups() {
+            let members: Vec<usize> = members.into_iter().filter(|&i| self.is_visible(i)).collect();
+            if members.is_empty() {
+                continue;
+            }
+            let collapsed = !self.has_filter() && self.collapsed.contains(&group);
+            rows.push(Row::Header(group));
+            if !collapsed {
+                rows.extend(members.into_iter().map(Row::Sound));
             }
         }
-        0
+        rows
+    }
+
+    pub fn start_search(&mut self) {
+        self.search.get_or_insert_with(String::new);
+        self.search_typing = true;
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.search_typing
+    }
+
+    pub fn has_search(&self) -> bool {
+        self.search.is_some()
+    }
+
+    /// Drops the filter and shows the full list, keeping the selection.
+    pub fn clear_search(&mut self) {
+        self.search = None;
+        self.search_typing = false;
+        self.apply_filter();
+    }
+
+    /// Shows only favorite sounds, or every sound again.
+    pub fn toggle_favorites_only(&mut self) {
+        self.favorites_only = !self.favorites_only;
+        self.apply_filter();
+    }
+
+    /// Marks the selected sound as favorite, or unmarks it, and saves it.
+    fn toggle_selected_favorite(&mut self) {
+        let Some((sound, _)) = self.get_selected_sound_mut() else {
+            return;
+        };
+        sound.toggle_favorite();
+        let favorite = sound.is_favorite();
+        let sound = sound.clone();
+        self.save_sound(&sound, |config| config.favorite = favorite);
+        self.apply_filter();
     }
 
-    fn select_next_sound(&mut self) -> usize {
-        for (i, sound) in self.sounds_list.iter_mut().enumerate() {
-            if sound.is_selected() {
-                sound.toggle_selection();
-                let next_index = if i == self.sounds_list.len() - 1 { self.sounds_list.len() - 1 } else { i + 1 };
-                self.sounds_list[next_index].toggle_selection();
-                return next_index;
+    /// Changes the `sounds.toml` entry of `sound` in place.
+    fn save_sound(&mut self, sound: &SoundItem, change: impl FnOnce(&mut SoundConfig)) {
+        let mut document = match ConfigDocument::load() {
+            Ok(document) => document,
+            Err(e) => {
+                self.notifications.push(Notification::error(format!("Could not read the sounds file: {}", e)));
+                return;
             }
+        };
+        let Some(mut config) = document.get_sound(sound.get_name(), sound.get_path()) else {
+            self.notifications.push(Notification::error(format!("Sound {} not found in the sounds file", sound.get_name())));
+            return;
+        };
+        change(&mut config);
+        document.update_sound(sound.get_name(), sound.get_path(), &config);
+        self.save_document(&document);
+    }
+
+    /// Writes `document` to `sounds.toml` and notes that the library changed.
+    fn save_document(&mut self, document: &ConfigDocument) {
+        self.library_changed = true;
+        if let Err(e) = document.save() {
+            self.notifications.push(Notification::error(format!("Could not write the sounds file: {}", e)));
         }
-        0
+    }
+
+    /// Refreshes the highlights and moves the selection onto a visible row.
+    fn apply_filter(&mut self) {
+        for sound in &mut self.sounds_list {
+            let highlight = match &self.search {
+                Some(query) => fuzzy_match(query, sound.get_name()).unwrap_or_default(),
+                None => vec![],
+            };
+            sound.set_highlight(highlight);
+        }
+        let rows = self.rows();
+        if self.selected_row(&rows).is_none() {
+            let first = rows.iter().find(|row| matches!(row, Row::Sound(_))).or(rows.first()).cloned();
+            if let Some(row) = first {
+                self.select_row(&row);
+            }
+        }
+        self.lower_bound = 0;
+        self.scroll_to_selection();
+    }
+
+    /// Text typed while searching edits the query.
+    pub fn handle_search_input(&mut self, key: KeyCode) {
+        let Some(query) = &mut self.search else {
+            return;
+        };
+        match key {
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Char(c) => query.push(c),
+            _ => return,
+        }
+        self.apply_filter();
+    }
+
+    /// Stops typing but keeps the filter, unless the query is empty.
+    fn confirm_search(&mut self) {
+        self.search_typing = false;
+        if self.search.as_deref() == Some("") {
+            self.clear_search();
+        }
+    }
+
+    fn selected_row(&self, rows: &[Row]) -> Option<usize> {
+        match &self.selected_header {
+            Some(header) => rows.iter().position(|row| matches!(row, Row::Header(h) if h == header)),
+            None => {
+                let selected = self.sounds_list.iter().position(|item| item.is_selected())?;
+                rows.iter().position(|row| *row == Row::Sound(selected))
+            }
+        }
+    }
+
+    fn select_row(&mut self, row: &Row) {
+        for sound in &mut self.sounds_list {
+            sound.set_selected(false);
+        }
+        self.selected_header = None;
+        match row {
+            Row::Header(header) => self.selected_header = Some(header.clone()),
+            Row::Sound(index) => self.sounds_list[*index].set_selected(true),
+        }
+    }
+
+    /// In the grid, `lower_bound` is the first line shown.
+    fn scroll_grid_to_selection(&mut self) {
+        let lines = self.grid_lines();
+        let Some((line, _)) = self.grid_position(&lines) else {
+            return;
+        };
+        self.lower_bound = self.lower_bound.min(line);
+        let height = |lines: &[GridLine]| lines.iter().map(|grid_line| grid_line.height() as usize).sum::<usize>();
+        while self.lower_bound < line && height(&lines[self.lower_bound..=line]) > self.page_size {
+            self.lower_bound += 1;
+        }
+        // Show earlier lines rather than leave space below the last one
+        while self.lower_bound > 0 && height(&lines[self.lower_bound - 1..]) <= self.page_size {
+            self.lower_bound -= 1;
+        }
+    }
+
+    /// Moves the selection by `delta` rows, staying within the list.
+    fn move_selection(&mut self, delta: isize) {
+        let rows = self.rows();
+        if rows.is_empty() {
+            return;
+        }
+        let current = self.selected_row(&rows).unwrap_or(0);
+        let next = current.saturating_add_signed(delta).min(rows.len() - 1);
+        self.select_row(&rows[next]);
+        self.scroll_to_selection();
+    }
+
+    pub fn get_view(&self) -> View {
+        self.view
+    }
+
+    pub fn set_view(&mut self, view: View) {
+        self.view = view;
+        self.lower_bound = 0;
+        self.scroll_to_selection();
+    }
+
+    fn grid_lines(&self) -> Vec<GridLine> {
+        let mut lines = vec![];
+        for row in self.rows() {
+            match row {
+                Row::Header(group) => lines.push(GridLine::Header(group)),
+                Row::Sound(index) => match lines.last_mut() {
+                    Some(GridLine::Cards(cards)) if cards.len() < self.columns => cards.push(index),
+                    _ => lines.push(GridLine::Cards(vec![index])),
+                },
+            }
+        }
+        lines
+    }
+
+    /// Line and column of the selection in the grid.
+    fn grid_position(&self, lines: &[GridLine]) -> Option<(usize, usize)> {
+        lines.iter().enumerate().find_map(|(line, grid_line)| {
+            self.selected_row(&grid_line.rows()).map(|column| (line, column))
+        })
+    }
+
+    /// Moves the selection `delta` lines up or down the grid, keeping the
+    /// column where the line is long enough.
+    fn move_grid_line(&mut self, delta: isize) {
+        let lines = self.grid_lines();
+        if lines.is_empty() {
+            return;
+        }
+        let (line, column) = self.grid_position(&lines).unwrap_or((0, 0));
+        let rows = lines[line.saturating_add_signed(delta).min(lines.len() - 1)].rows();
+        self.select_row(&rows[column.min(rows.len() - 1)]);
+        self.scroll_to_selection();
+    }
+
+    /// Lines moved by a page key in the grid: half of the cards that fit.
+    fn grid_page(&self) -> isize {
+        (self.page_size / CARD_HEIGHT as usize).div_ceil(2).max(1) as isize
+    }
+
+    /// Adjusts the visible window so the selected row is on screen.
+    fn scroll_to_selection(&mut self) {
+        if self.view == View::Grid {
+            return self.scroll_grid_to_selection();
+        }
+        let rows = self.rows();
+        let page = self.page_size.clamp(1, rows.len().max(1));
+        if let Some(row) = self.selected_row(&rows) {
+            if row < self.lower_bound {
+                self.lower_bound = row;
+            } else if row >= self.lower_bound + page {
+                self.lower_bound = row + 1 - page;
+            }
+        }
+        self.lower_bound = self.lower_bound.min(rows.len().saturating_sub(page));
+        self.upper_bound = (self.lower_bound + page - 1).min(rows.len().saturating_sub(1));
+    }
+
+    /// Swaps the selected sound with the visible sound above (`-1`) or below
+    /// (`1`) it, without crossing into another category.
+    fn move_selected_sound(&mut self, delta: isize) {
+        if self.selected_header.is_some() {
+            return;
+        }
+        let rows = self.rows();
+        let Some(current_row) = self.selected_row(&rows) else {
+            return;
+        };
+        let (Some(Row::Sound(current)), Some(Row::Sound(other))) =
+            (rows.get(current_row), current_row.checked_add_signed(delta).and_then(|row| rows.get(row)))
+        else {
+            return;
+        };
+        self.sounds_list.swap(*current, *other);
+        self.scroll_to_selection();
+        self.save_order();
+    }
+
+    pub fn sort_sounds(&mut self, order: SortOrder) {
+        match order {
+            SortOrder::Name => self.sounds_list.sort_by_key(|sound| sound.get_name().to_lowercase()),
+            SortOrder::Category => self.sounds_list.sort_by_key(|sound| {
+                (sound.get_category().map(str::to_lowercase), sound.get_name().to_lowercase())
+            }),
+            SortOrder::ActiveFirst => self.sounds_list.sort_by_key(|sound| !sound.is_active()),
+        }
+        self.scroll_to_selection();
+        self.save_order();
+    }
+
+    /// Writes the order of the list back to `sounds.toml`.
+    fn save_order(&mut self) {
+        let mut document = match ConfigDocument::load() {
+            Ok(document) => document,
+            Err(e) => {
+                self.notifications.push(Notification::error(format!("Could not read the sounds file: {}", e)));
+                return;
+            }
+        };
+        let order: Vec<(&str, &str)> = self.sounds_list.iter().map(|sound| (sound.get_name(), sound.get_path())).collect();
+        document.reorder_sounds(&order);
+        self.save_document(&document);
     }
 
     fn switch_play_pause_all(&mut self) {
         for sound_item in &mut self.sounds_list {
             if sound_item.is_active() {
                 sound_item.switch_play_pause();
-            } 
+            }
         }
     }
 
@@ -74,33 +659,33 @@ impl SoundsBlock {
             None => return,
         };
         let sound = self.sounds_list[current_index].clone();
-        
-        if current_index >= self.sounds_list.len() - 1 {
-            self.select_previous_sound();
-        } else {
-            self.select_next_sound();
+
+        // The selection flag moves with the item, so select the neighbour first
+        let rows = self.rows();
+        let current_row = self.selected_row(&rows).unwrap_or(0);
+        let neighbour = rows.get(current_row + 1).or_else(|| current_row.checked_sub(1).and_then(|i| rows.get(i))).cloned();
+        match neighbour {
+            Some(row) => self.select_row(&row),
+            None => self.selected_header = None,
         }
+
         self.delete_selected_sound_from_file(&sound);
         self.sounds_list.remove(current_index);
-        if current_index < self.lower_bound {
-            self.lower_bound -= 1;
-            self.upper_bound -= 1;
-        } else if current_index <= self.upper_bound {
-            self.upper_bound -= 1;
-        }
+        self.scroll_to_selection();
     }
 
     fn delete_selected_sound_from_file(&mut self, sound: &SoundItem) {
-        let sounds_file = std::fs::read_to_string(RESOURCES_PATH.to_string() + "sounds.toml");
-        if let Err(e) = sounds_file {
-            eprintln!("Error reading sounds file: {}", e);
+        let mut document = match ConfigDocument::load() {
+            Ok(document) => document,
+            Err(e) => {
+                self.notifications.push(Notification::error(format!("Could not read the sounds file: {}", e)));
+                return;
+            }
+        };
+        if !document.remove_sound(sound.get_name(), sound.get_path()) {
             return;
         }
-        let toml_file = sounds_file.unwrap();
-        let mut config: Config = toml::from_str(&toml_file).unwrap();
-        config.sound.retain(|s| s.name != sound.get_name() && s.file != sound.get_path());
-        let new_toml = toml::to_string(&config).unwrap();
-        std::fs::write(RESOURCES_PATH.to_string() + "sounds.toml", new_toml).unwrap();
+        self.save_document(&document);
     }
 
     fn change_volume_mult(&mut self, delta: f32) {
@@ -110,52 +695,309 @@ impl SoundsBlock {
         }
     }
 
+    /// Group the selection belongs to: the selected header, or the category
+    /// of the selected sound.
+    fn selected_group(&self) -> Option<String> {
+        self.selected_header
+            .clone()
+            .or_else(|| self.get_selected_sound().map(|sound| Self::group_of(sound).to_string()))
+    }
+
+    fn toggle_collapsed(&mut self, group: &str) {
+        if !self.collapsed.remove(group) {
+            self.collapsed.insert(group.to_string());
+        }
+        self.scroll_to_selection();
+    }
+
+    fn toggle_group_mute(&mut self, group: &str) {
+        if !self.muted_groups.remove(group) {
+            self.muted_groups.insert(group.to_string());
+        }
+        self.apply_group_audibility();
+    }
+
+    fn toggle_group_solo(&mut self, group: &str) {
+        self.solo_group = match &self.solo_group {
+            Some(solo) if solo == group => None,
+            _ => Some(group.to_string()),
+        };
+        self.solo_sound = None;
+        self.apply_group_audibility();
+    }
+
+    fn change_group_volume(&mut self, group: &str, delta: f32) {
+        let mult = self.volume_mult;
+        for sound in self.sounds_list.iter_mut().filter(|sound| Self::group_of(sound) == group) {
+            sound.change_volume(delta, mult);
+        }
+    }
+
+    /// Index of the sound called `name`, ignoring case, or of the only sound
+    /// whose name starts with it.
+    fn find_sound(&self, name: &str) -> Result<usize, String> {
+        let lowercase = name.to_lowercase();
+        if let Some(index) = self.sounds_list.iter().position(|sound| sound.get_name().to_lowercase() == lowercase) {
+            return Ok(index);
+        }
+        let mut matches = self.sounds_list.iter().enumerate().filter(|(_, sound)| sound.get_name().to_lowercase().starts_with(&lowercase));
+        match (matches.next(), matches.next()) {
+            (Some((index, _)), None) => Ok(index),
+            (Some(_), Some(_)) => Err(format!("{:?} matches several sounds", name)),
+            _ => Err(format!("no sound called {:?}", name)),
+        }
+    }
+
+    /// Sets the volume of the sound called `name` and returns its full name.
+    pub fn set_sound_volume(&mut self, name: &str, volume: f32) -> Result<String, String> {
+        let index = self.find_sound(name)?;
+        let sound = &mut self.sounds_list[index];
+        sound.set_volume(volume, self.volume_mult);
+        Ok(sound.get_name().to_string())
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.change_volume_mult(volume - self.volume_mult);
+    }
+
+    /// Solos the category or the sound called `name`, or unsolos it when it
+    /// already is. Returns the name and whether it is now soloed.
+    pub fn toggle_solo(&mut self, name: &str) -> Result<(String, bool), String> {
+        if let Some((group, _)) = self.groups().into_iter().find(|(group, _)| group.eq_ignore_ascii_case(name)) {
+            self.toggle_group_solo(&group);
+            let soloed = self.solo_group.is_some();
+            return Ok((group, soloed));
+        }
+        let sound = self.sounds_list[self.find_sound(name)?].get_name().to_string();
+        self.solo_sound = match &self.solo_sound {
+            Some(solo) if *solo == sound => None,
+            _ => Some(sound.clone()),
+        };
+        self.solo_group = None;
+        self.apply_group_audibility();
+        let soloed = self.solo_sound.is_some();
+        Ok((sound, soloed))
+    }
+
+    /// Silences the sounds of muted groups, and every other sound while a
+    /// group or a sound is soloed.
+    fn apply_group_audibility(&mut self) {
+        let mult = self.volume_mult;
+        for sound in &mut self.sounds_list {
+            let group = Self::group_of(sound);
+            let muted = self.muted_groups.contains(group)
+                || self.solo_group.as_deref().is_some_and(|solo| solo != group)
+                || self.solo_sound.as_deref().is_some_and(|solo| solo != sound.get_name());
+            sound.set_muted(muted, mult);
+        }
+    }
+
+    /// Sets the master volume and activates exactly the sounds in the preset.
+    pub fn apply_preset(&mut self, preset: &PresetConfig, general_play_status: bool) {
+        self.volume_mult = preset.master_volume.clamp(0.0, 1.0);
+        for sound in &mut self.sounds_list {
+            match preset.sounds.get(sound.get_name()) {
+                Some(volume) => {
+                    sound.set_volume(*volume, self.volume_mult);
+                    sound.set_active(true, general_play_status);
+                }
+                None => {
+                    sound.change_volume(0.0, self.volume_mult);
+                    sound.set_active(false, general_play_status);
+                }
+            }
+        }
+    }
+
+    /// Captures the current mix: the master volume and every active sound.
+    pub fn to_preset(&self, name: String) -> PresetConfig {
+        let sounds = self.sounds_list
+            .iter()
+            .filter(|sound| sound.is_active())
+            .map(|sound| (sound.get_name().to_string(), sound.get_volume()))
+            .collect();
+        PresetConfig { name, master_volume: self.volume_mult, sounds }
+    }
+
     pub fn get_mult(&self) -> f32 {
         self.volume_mult
     }
 
-    pub fn handle_key_event(&mut self, key: KeyCode, general_play_status: bool) {
-        match key {
-            KeyCode::Up => {
-                let i = self.select_previous_sound();
-                if self.lower_bound > i {
-                    self.lower_bound -= 1;
-                    self.upper_bound -= 1;
+    pub fn handle_action(&mut self, action: Action, general_play_status: bool) {
+        match action {
+            Action::SelectFirst => self.move_selection(isize::MIN),
+            Action::SelectLast => self.move_selection(isize::MAX),
+            Action::PlayPauseAll => { self.switch_play_pause_all(); }
+            Action::SelectPrevious if self.view == View::Grid => self.move_grid_line(-1),
+            Action::SelectNext if self.view == View::Grid => self.move_grid_line(1),
+            Action::PageUp if self.view == View::Grid => self.move_grid_line(-self.grid_page()),
+            Action::PageDown if self.view == View::Grid => self.move_grid_line(self.grid_page()),
+            Action::SelectLeft | Action::SelectPrevious => self.move_selection(-1),
+            Action::SelectRight | Action::SelectNext => self.move_selection(1),
+            Action::PageUp => self.move_selection(-(self.page_size.div_ceil(2) as isize)),
+            Action::PageDown => self.move_selection(self.page_size.div_ceil(2) as isize),
+            Action::ToggleFavorite => self.toggle_selected_favorite(),
+            Action::FavoritesOnly => self.toggle_favorites_only(),
+            Action::MoveUp => self.move_selected_sound(-1),
+            Action::MoveDown => self.move_selected_sound(1),
+            Action::MasterVolumeUp => self.change_volume_mult(0.05),
+            Action::MasterVolumeDown => self.change_volume_mult(-0.05),
+            Action::Search => self.start_search(),
+            Action::ClearSearch => self.clear_search(),
+            Action::ConfirmSearch => self.confirm_search(),
+            Action::MuteGroup => {
+                if let Some(group) = self.selected_group() {
+                    self.toggle_group_mute(&group);
                 }
             }
-            KeyCode::Down => {
-                let i = self.select_next_sound();
-                if self.upper_bound < i {
-                    self.lower_bound += 1;
-                    self.upper_bound += 1;
+            Action::SoloGroup => {
+                if let Some(group) = self.selected_group() {
+                    self.toggle_group_solo(&group);
                 }
-            } 
-            KeyCode::Enter => { self.switch_play_pause_all(); }
-            KeyCode::PageUp => {
-                if self.lower_bound > 0 {
-                    let page_size = (self.upper_bound - self.lower_bound + 1) / 2;
-                    self.lower_bound = self.lower_bound.saturating_sub(page_size);
-                    self.upper_bound = (self.upper_bound - page_size).min(self.sounds_list.len() - 1);
+            }
+            _ => {
+                if let Some(header) = self.selected_header.clone() {
+                    match action {
+                        Action::ToggleSound => self.toggle_collapsed(&header),
+                        Action::VolumeDown => self.change_group_volume(&header, -0.05),
+                        Action::VolumeUp => self.change_group_volume(&header, 0.05),
+                        _ => {}
+                    }
+                    return;
                 }
+                let mult = self.volume_mult;
+                if let Some((selected_sound, _)) = self.get_selected_sound_mut()
+                    && let Err(e) = selected_sound.handle_action(action, general_play_status, mult) {
+                    self.notifications.push(Notification::error(format!("Could not handle the key: {}", e)));
+                }
+            }
+        }
+    }
+
+    /// Rows on screen when the block is drawn in `area`, with where each one
+    /// goes: a line of the list, or a header or card of the grid.
+    fn visible_rows(&self, area: Rect) -> Vec<(Row, Rect)> {
+        if self.view == View::Grid {
+            return self.visible_cards(area);
+        }
+        let rows = self.rows();
+        if rows.is_empty() {
+            return vec![];
+        }
+        let num_chunks = (area.height as usize).saturating_sub(4).clamp(1, rows.len());
+        let constraints = vec![Constraint::Length(1); num_chunks];
+        let min = self.lower_bound.min(rows.len() - num_chunks);
+        let chunks = Layout::default()
+            .direction(ratatui::layout::Direction::Vertical)
+            .constraints(constraints)
+            .margin(2)
+            .split(area);
+        rows.into_iter().skip(min).zip(chunks.iter().copied()).collect()
+    }
+
+    fn visible_cards(&self, area: Rect) -> Vec<(Row, Rect)> {
+        let inner = area.inner(Margin::new(2, 2));
+        let card_width = inner.width / self.columns as u16;
+        let mut visible = vec![];
+        let mut y = inner.y;
+        for line in self.grid_lines().iter().skip(self.lower_bound) {
+            if y + line.height() > inner.bottom() && !visible.is_empty() {
+                break;
             }
-            KeyCode::PageDown => {
-                if self.upper_bound < self.sounds_list.len() - 1 {
-                    let page_size = (self.upper_bound - self.lower_bound + 1) / 2;
-                    self.lower_bound = (self.lower_bound + page_size).min(self.sounds_list.len() - 1);
-                    self.upper_bound = (self.upper_bound + page_size).min(self.sounds_list.len() - 1);
+            match line {
+                GridLine::Header(group) => visible.push((Row::Header(group.clone()), Rect::new(inner.x, y, inner.width, 1))),
+                GridLine::Cards(cards) => {
+                    for (column, index) in cards.iter().enumerate() {
+                        let card = Rect::new(inner.x + column as u16 * card_width, y, card_width, line.height());
+                        visible.push((Row::Sound(*index), card.intersection(inner)));
+                    }
                 }
             }
-            KeyCode::Char('d') => self.delete_selected_sound_from_list(),
-            KeyCode::Char('+') => self.change_volume_mult(0.05),
-            KeyCode::Char('-') => self.change_volume_mult(-0.05),
-            _ => {
-                let mult = self.volume_mult;
-                if let Some((selected_sound, _)) = self.get_selected_sound_mut() {
-                    if let Err(e) = selected_sound.handle_key_event(key, general_play_status, mult) {
-                        eprintln!("Error handling key event: {}", e);
+            y += line.height();
+        }
+        visible
+    }
+
+    /// Where the volume of a sound drawn in `area` is.
+    fn volume_area(&self, area: Rect) -> Rect {
+        match self.view {
+            View::List => SoundItem::volume_area(area),
+            View::Grid => SoundItem::card_volume_area(area),
+        }
+    }
+
+    /// Scrolls the list by `delta` rows without moving the selection.
+    fn scroll(&mut self, delta: isize) {
+        if self.view == View::Grid {
+            let lines = self.grid_lines().len();
+            self.lower_bound = self.lower_bound.saturating_add_signed(delta).min(lines.saturating_sub(1));
+            return;
+        }
+        let rows = self.rows().len();
+        let page = self.page_size.clamp(1, rows.max(1));
+        self.lower_bound = self.lower_bound.saturating_add_signed(delta).min(rows.saturating_sub(page));
+        self.upper_bound = (self.lower_bound + page - 1).min(rows.saturating_sub(1));
+    }
+
+    /// Sets the volume of the sound at `index` from where the volume area of
+    /// its row, drawn in `row_area`, was clicked.
+    fn set_volume_at(&mut self, index: usize, row_area: Rect, column: u16) {
+        let volume_area = VolumeGauge::bar_area(self.volume_area(row_area));
+        let offset = column.saturating_sub(volume_area.x).min(volume_area.width.saturating_sub(1));
+        let volume = offset as f32 / volume_area.width.saturating_sub(1).max(1) as f32;
+        let mult = self.volume_mult;
+        self.sounds_list[index].set_volume((volume * 100.0).round() / 100.0, mult);
+    }
+
+    /// Handles a mouse event over the block drawn in `area`. Clicking a row
+    /// selects it and clicking it again toggles it, clicking or dragging on
+    /// the volume sets it, and the wheel changes the volume of the sound
+    /// under the pointer or scrolls the list. Returns the action the event
+    /// amounts to, if any.
+    pub fn handle_mouse_event(&mut self, event: MouseEvent, area: Rect, general_play_status: bool) -> Option<Action> {
+        let position = Position::new(event.column, event.row);
+        let hit = self.visible_rows(area).into_iter().find(|(_, row_area)| row_area.contains(position));
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let (row, row_area) = hit?;
+                let was_selected = self.selected_row(std::slice::from_ref(&row)).is_some();
+                self.select_row(&row);
+                match row {
+                    Row::Sound(index) if self.volume_area(row_area).contains(position) => {
+                        self.dragging = Some(index);
+                        self.set_volume_at(index, row_area, event.column);
                     }
-                } 
+                    _ if was_selected => self.handle_action(Action::ToggleSound, general_play_status),
+                    _ => {}
+                }
+                None
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let index = self.dragging?;
+                let row_area = self.visible_rows(area).into_iter().find(|(row, _)| *row == Row::Sound(index))?.1;
+                self.set_volume_at(index, row_area, event.column);
+                None
+            }
+            MouseEventKind::Up(MouseButton::Left) => {
+                self.dragging = None;
+                None
             }
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let up = event.kind == MouseEventKind::ScrollUp;
+                match hit {
+                    Some((row @ Row::Sound(_), _)) => {
+                        self.select_row(&row);
+                        let action = if up { Action::VolumeUp } else { Action::VolumeDown };
+                        self.handle_action(action, general_play_status);
+                        Some(action)
+                    }
+                    _ => {
+                        self.scroll(if up { -1 } else { 1 });
+                        None
+                    }
+                }
+            }
+            _ => None,
         }
     }
 
@@ -165,53 +1007,75 @@ impl SoundsBlock {
             self.upper_bound = 0;
             return;
         }
-        let selected_info = if let Some((_, index)) = self.get_selected_sound_mut() {
-            Some(index)
-        } else {
-            None
-        };
-         
-        let num_chunks = (area.height as usize - 4).clamp(1, self.sounds_list.len());
-        self.upper_bound = (self.lower_bound + num_chunks - 1).clamp(self.lower_bound, self.sounds_list.len() - 1);
-        
-        if let Some(index_fixed) = selected_info {
-            if self.upper_bound < index_fixed {
-                self.sounds_list[index_fixed].toggle_selection();
-                self.sounds_list[self.upper_bound].toggle_selection();
-            }
+        self.page_size = (area.height as usize).saturating_sub(4).max(1);
+        self.columns = (area.width.saturating_sub(4) / CARD_WIDTH).max(1) as usize;
+        if self.selected_header.is_none() && self.get_selected_sound().is_none()
+            && let Some(row) = self.rows().first().cloned() {
+            self.select_row(&row);
         }
+        self.scroll_to_selection();
+    }
+
+    fn render_header(&self, group: &str, area: Rect, buf: &mut Buffer) {
+        let members: Vec<&SoundItem> = self.sounds_list.iter().filter(|sound| Self::group_of(sound) == group).collect();
+        let active = members.iter().filter(|sound| sound.is_active()).count();
+        let arrow = if self.collapsed.contains(group) { "▶" } else { "▼" };
+        let mut text = format!("{} {} ({}/{})", arrow, group, active, members.len());
+        if self.muted_groups.contains(group) {
+            text.push_str("  [muted]");
+        }
+        if self.solo_group.as_deref() == Some(group) {
+            text.push_str("  [solo]");
+        }
+        let theme = theme::current();
+        let mut style = theme.header;
+        if self.selected_header.as_deref() == Some(group) {
+            style = style.patch(theme.selected);
+        }
+        Paragraph::new(text).style(style).render(area, buf);
     }
 }
 
 impl Widget for &SoundsBlock {
     fn render(self, area: Rect, buf: &mut Buffer) {
         let title = Line::from(" Sounds ".bold());
-        let block = Block::bordered()
+        let mut block = Block::bordered()
             .border_set(border::THICK)
+            .border_style(theme::current().border)
             .title(title)
-            .title_alignment(ratatui::layout::Alignment::Center);
+            .title_alignment(Alignment::Center);
+        if let Some(query) = &self.search {
+            let cursor = if self.search_typing { "█" } else { "" };
+            block = block.title_bottom(Line::from(format!(" /{}{} ", query, cursor)).left_aligned());
+        }
+        if self.favorites_only {
+            block = block.title_bottom(Line::from(" ★ Favorites ").right_aligned());
+        }
         block.render(area, buf);
 
-        if self.sounds_list.is_empty() {
+        let rows = self.rows();
+        if rows.is_empty() && self.has_filter() {
+            Paragraph::new("No sounds match the filter")
+                .style(theme::current().highlight)
+                .alignment(Alignment::Center)
+                .render(area.inner(Margin::new(1, 2)), buf);
+            return;
+        }
+        if rows.is_empty() {
             // Display a message when no sounds are available
-            let paragraph = ratatui::widgets::Paragraph::new("No sound files found in the sounds directory")
-                .style(ratatui::style::Style::default().fg(ratatui::style::Color::Yellow))
-                .alignment(ratatui::layout::Alignment::Center);
+            let paragraph = Paragraph::new("No sound files found in the sounds directory")
+                .style(theme::current().highlight)
+                .alignment(Alignment::Center);
             paragraph.render(area, buf);
             return;
         }
 
-        let num_chunks = (area.height as usize - 4).clamp(1, self.sounds_list.len());
-        let constraints = vec![Constraint::Length(1); num_chunks];
-        let min = self.lower_bound.clamp(0, self.sounds_list.len() - num_chunks);
-        let max = (self.lower_bound + num_chunks - 1).clamp(min, self.sounds_list.len() - 1);
-        let chunks = Layout::default()
-            .direction(ratatui::layout::Direction::Vertical)
-            .constraints(constraints)
-            .margin(2)
-            .split(area);
-        for (i, sound_item) in self.sounds_list[self.lower_bound..=max].iter().enumerate() {
-            sound_item.render(chunks[i], buf);
+        for (row, row_area) in self.visible_rows(area) {
+            match row {
+                Row::Header(group) => self.render_header(&group, row_area, buf),
+                Row::Sound(index) if self.view == View::Grid => self.sounds_list[index].render_card(row_area, buf),
+                Row::Sound(index) => self.sounds_list[index].render(row_area, buf),
+            }
         }
     }
 }
@@ -0,0 +1,92 @@
+use std::sync::OnceLock;
+
+use ratatui::{buffer::Buffer, layout::{Alignment, Rect}, style::Style, text::Line, widgets::Widget};
+use crate::{components::level_meter::{cell, HORIZONTAL_EIGHTHS}, theme};
+
+/// Width kept for the label at the right of the bar, enough for `100%→100%`.
+pub const LABEL_WIDTH: u16 = 10;
+
+/// Whether the locale says the terminal can show Unicode. Checked once.
+pub fn supports_unicode() -> bool {
+    static UNICODE: OnceLock<bool> = OnceLock::new();
+    *UNICODE.get_or_init(|| {
+        if cfg!(windows) {
+            return true;
+        }
+        ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .is_some_and(|value| {
+                let value = value.to_lowercase();
+                value.contains("utf-8") || value.contains("utf8")
+            })
+    })
+}
+
+/// A horizontal volume bar filling the width it is given, followed by the
+/// percentage. The bar is filled up to the set volume in eighths of a cell,
+/// and the part above the effective level (the volume times the master
+/// volume, or nothing when muted) is drawn like the empty track. Only ASCII
+/// is used when the terminal lacks Unicode.
+pub struct VolumeGauge {
+    volume: f32,
+    effective: f32,
+    style: Style,
+    unicode: bool,
+}
+
+impl VolumeGauge {
+    pub fn new(volume: f32, effective: f32, style: Style) -> Self {
+        VolumeGauge {
+            volume: volume.clamp(0.0, 1.0),
+            effective: effective.clamp(0.0, 1.0),
+            style,
+            unicode: supports_unicode(),
+        }
+    }
+
+    /// `40%`, or `40%→32%` when the effective level differs.
+    pub fn label(&self) -> String {
+        let volume = (self.volume * 100.0).round();
+        let effective = (self.effective * 100.0).round();
+        if volume == effective {
+            format!("{:.0}%", volume)
+        } else {
+            format!("{:.0}%{}{:.0}%", volume, if self.unicode { "→" } else { ">" }, effective)
+        }
+    }
+
+    /// Where the bar is drawn in `area`, left of the label. Clicks on it map
+    /// to volumes from 0 at the left to 1 at the right.
+    pub fn bar_area(area: Rect) -> Rect {
+        let label_width = if area.width >= LABEL_WIDTH * 2 { LABEL_WIDTH } else { 0 };
+        Rect::new(area.x, area.y, area.width - label_width, area.height.min(1))
+    }
+}
+
+impl Widget for &VolumeGauge {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.is_empty() {
+            return;
+        }
+        let theme = theme::current();
+        let bar = VolumeGauge::bar_area(area);
+        let filled = (self.volume * bar.width as f32 * 8.0).round() as u32;
+        let audible = self.effective * bar.width as f32;
+        let track = if self.unicode { '─' } else { '-' };
+        for i in 0..bar.width {
+            let eighths = filled.saturating_sub(i as u32 * 8).min(8);
+            let (symbol, style) = match cell(eighths, &HORIZONTAL_EIGHTHS, '=', self.unicode) {
+                Some(symbol) if i as f32 + eighths as f32 / 16.0 <= audible => (symbol, self.style.patch(theme.gauge)),
+                Some(symbol) => (symbol, self.style.patch(theme.gauge_empty)),
+                None => (track, self.style.patch(theme.gauge_empty)),
+            };
+            buf[(bar.x + i, bar.y)].set_char(symbol).set_style(style);
+        }
+        if bar.width < area.width {
+            let label = Rect::new(bar.right(), area.y, area.width - bar.width, 1);
+            Line::from(self.label()).style(self.style).alignment(Alignment::Right).render(label, buf);
+        }
+    }
+}
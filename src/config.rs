@@ -1,13 +1,455 @@
+use std::{collections::BTreeMap, fs, io::{self, Write}, path::{Path, PathBuf}};
 use serde::{Deserialize, Serialize};
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, InlineTable, Item, Table};
+use crate::app::{resolve_sound_path, RESOURCES_PATH};
+
+pub const CONFIG_FILE: &str = "sounds.toml";
+
+/// Schema version written by this build. Bump it and append a step to
+/// `MIGRATIONS` whenever the layout of `sounds.toml` changes.
+pub const CONFIG_VERSION: u32 = 5;
+
+/// Keys of a `[[sound]]` entry that are left out when they have no value.
+const OPTIONAL_SOUND_KEYS: &[&str] = &["category", "tags", "favorite", "author", "license", "source_url"];
+
+/// Number of previous versions of `sounds.toml` kept as `sounds.toml.bak.N`.
+pub const BACKUP_COUNT: usize = 3;
+
+/// `MIGRATIONS[n]` upgrades a document from version `n` to version `n + 1`.
+/// Each version so far only added keys that may be left out, so the steps
+/// have nothing to change besides `version`:
+///
+/// 1. `version` itself.
+/// 2. `[[preset]]` tables.
+/// 3. `category` of sounds.
+/// 4. `tags` and `favorite` of sounds.
+/// 5. `author`, `license` and `source_url` of sounds.
+const MIGRATIONS: &[fn(&mut DocumentMut)] = &[added_optional_keys, added_optional_keys, added_optional_keys, added_optional_keys, added_optional_keys];
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
     pub sound: Vec<SoundConfig>,
+    #[serde(default)]
+    pub preset: Vec<PresetConfig>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct SoundConfig {
     pub name: String,
     pub file: String,
     pub icon: String,
-}
\ No newline at end of file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub favorite: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+}
+
+/// A saved mix: the master volume and the volume of every active sound.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PresetConfig {
+    pub name: String,
+    pub master_volume: f32,
+    /// Volumes keyed by sound name. Sounds not listed are inactive.
+    #[serde(default)]
+    pub sounds: BTreeMap<String, f32>,
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        PathBuf::from(RESOURCES_PATH).join(CONFIG_FILE)
+    }
+
+    pub fn load() -> io::Result<Self> {
+        ConfigDocument::load()?.config()
+    }
+}
+
+impl SoundConfig {
+    fn from_table(table: &Table) -> Option<Self> {
+        toml::from_str(&table.to_string()).ok()
+    }
+
+    fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        table["name"] = value(&self.name);
+        table["file"] = value(&self.file);
+        table["icon"] = value(&self.icon);
+        if let Some(category) = &self.category {
+            table["category"] = value(category);
+        }
+        if !self.tags.is_empty() {
+            table["tags"] = value(self.tags.iter().collect::<Array>());
+        }
+        if self.favorite {
+            table["favorite"] = value(true);
+        }
+        for (key, field) in [("author", &self.author), ("license", &self.license), ("source_url", &self.source_url)] {
+            if let Some(field) = field {
+                table[key] = value(field);
+            }
+        }
+        table
+    }
+}
+
+impl PresetConfig {
+    fn to_table(&self) -> Table {
+        let mut sounds = InlineTable::new();
+        for (name, volume) in &self.sounds {
+            sounds.insert(name, round_volume(*volume).into());
+        }
+        let mut table = Table::new();
+        table["name"] = value(&self.name);
+        table["master_volume"] = value(round_volume(self.master_volume));
+        table["sounds"] = value(sounds);
+        table
+    }
+}
+
+/// Volumes move in steps of 0.05, so two decimals keep `f32` noise such as
+/// `0.550000011920929` out of the file.
+pub fn round_volume(volume: f32) -> f64 {
+    (volume as f64 * 100.0).round() / 100.0
+}
+
+/// Format-preserving view of `sounds.toml`. Every change to the library goes
+/// through this type so that comments and layout written by hand survive.
+pub struct ConfigDocument {
+    doc: DocumentMut,
+    from_version: u32,
+}
+
+impl ConfigDocument {
+    /// Reads `sounds.toml`, upgrading it in memory if it was written with an
+    /// older schema. Upgraded files are rewritten after saving a backup of the
+    /// original next to it.
+    pub fn load() -> io::Result<Self> {
+        let path = Config::path();
+        let contents = fs::read_to_string(&path)?;
+        let document = Self::parse(&contents)?;
+        if document.from_version < CONFIG_VERSION {
+            let backup = path.with_extension(format!("toml.v{}.bak", document.from_version));
+            fs::copy(&path, backup)?;
+            document.save()?;
+        }
+        Ok(document)
+    }
+
+    /// Parses a document of any known version.
+    pub fn parse(contents: &str) -> io::Result<Self> {
+        let mut doc: DocumentMut = contents.parse().map_err(invalid_data)?;
+        let from_version = migrate(&mut doc)?;
+        Ok(ConfigDocument { doc, from_version })
+    }
+
+    pub fn config(&self) -> io::Result<Config> {
+        toml::from_str(&self.doc.to_string()).map_err(invalid_data)
+    }
+
+    fn sounds_mut(&mut self) -> &mut ArrayOfTables {
+        if !self.doc.contains_array_of_tables("sound") {
+            self.doc["sound"] = Item::ArrayOfTables(ArrayOfTables::new());
+        }
+        self.doc["sound"].as_array_of_tables_mut().unwrap()
+    }
+
+    pub fn push_sound(&mut self, sound: &SoundConfig) {
+        self.sounds_mut().push(sound.to_table());
+    }
+
+    /// Index of the first entry called `name` whose file resolves to `path`.
+    fn find_sound(&self, name: &str, path: &str) -> Option<usize> {
+        let sounds = self.doc.get("sound").and_then(Item::as_array_of_tables)?;
+        sounds.iter().position(|sound| {
+            sound.get("name").and_then(Item::as_str) == Some(name)
+                && sound.get("file").and_then(Item::as_str).map(resolve_sound_path).as_deref() == Some(path)
+        })
+    }
+
+    pub fn get_sound(&self, name: &str, path: &str) -> Option<SoundConfig> {
+        let index = self.find_sound(name, path)?;
+        SoundConfig::from_table(self.doc["sound"].as_array_of_tables()?.get(index)?)
+    }
+
+    /// Replaces the fields of an existing entry in place, keeping its position
+    /// in the file and the comments attached to it.
+    pub fn update_sound(&mut self, name: &str, path: &str, sound: &SoundConfig) -> bool {
+        let Some(index) = self.find_sound(name, path) else {
+            return false;
+        };
+        let table = self.sounds_mut().get_mut(index).unwrap();
+        let new_table = sound.to_table();
+        for key in OPTIONAL_SOUND_KEYS {
+            if !new_table.contains_key(key) {
+                table.remove(key);
+            }
+        }
+        for (key, item) in new_table.iter() {
+            match (table.get_mut(key).and_then(Item::as_value_mut), item.as_value()) {
+                (Some(existing), Some(new_value)) => {
+                    // Keep the whitespace and trailing comment around the old value
+                    let decor = existing.decor().clone();
+                    *existing = new_value.clone();
+                    *existing.decor_mut() = decor;
+                }
+                _ => table[key] = item.clone(),
+            }
+        }
+        true
+    }
+
+    /// Removes the first entry called `name` whose file resolves to `path`.
+    pub fn remove_sound(&mut self, name: &str, path: &str) -> bool {
+        match self.find_sound(name, path) {
+            Some(index) => {
+                self.sounds_mut().remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Rearranges the entries to follow `order`, given as name and resolved
+    /// path pairs. Entries not in `order` keep their relative order at the end.
+    pub fn reorder_sounds(&mut self, order: &[(&str, &str)]) {
+        let mut indexes: Vec<usize> = vec![];
+        for (name, path) in order {
+            if let Some(index) = self.find_sound(name, path)
+                && !indexes.contains(&index) {
+                indexes.push(index);
+            }
+        }
+        let sounds = self.sounds_mut();
+        indexes.extend((0..sounds.len()).filter(|i| !indexes.contains(i)).collect::<Vec<usize>>());
+        let tables: Vec<Table> = sounds.iter().cloned().collect();
+        sounds.clear();
+        for (slot, &index) in indexes.iter().enumerate() {
+            // Tables print in document position order, so each slot keeps the
+            // position of the table that was there before
+            let mut table = tables[index].clone();
+            if let Some(position) = tables[slot].position() {
+                table.set_position(position);
+            }
+            let prefix = table.decor().prefix().and_then(|prefix| prefix.as_str()).unwrap_or_default().to_string();
+            if slot > 0 && !prefix.starts_with('\n') {
+                table.decor_mut().set_prefix(format!("\n{}", prefix));
+            }
+            sounds.push(table);
+        }
+    }
+
+    /// Makes the `[[sound]]` entries match `sounds`: missing entries are added,
+    /// changed ones updated in place and the rest removed, then the entries
+    /// are put in the order of `sounds`.
+    pub fn set_sounds(&mut self, sounds: &[SoundConfig]) {
+        let keys: Vec<(&str, String)> = sounds.iter().map(|s| (s.name.as_str(), resolve_sound_path(&s.file))).collect();
+        self.sounds_mut().retain(|table| {
+            let name = table.get("name").and_then(Item::as_str).unwrap_or_default();
+            let path = table.get("file").and_then(Item::as_str).map(resolve_sound_path).unwrap_or_default();
+            keys.iter().any(|(n, p)| *n == name && *p == path)
+        });
+        for (sound, (name, path)) in sounds.iter().zip(&keys) {
+            match self.get_sound(name, path) {
+                Some(existing) if existing == *sound => {}
+                Some(_) => {
+                    self.update_sound(name, path, sound);
+                }
+                None => self.push_sound(sound),
+            }
+        }
+        let order: Vec<(&str, &str)> = keys.iter().map(|(name, path)| (*name, path.as_str())).collect();
+        self.reorder_sounds(&order);
+    }
+
+    /// Stores `preset`, replacing any preset with the same name.
+    pub fn set_preset(&mut self, preset: &PresetConfig) {
+        if !self.doc.contains_array_of_tables("preset") {
+            self.doc["preset"] = Item::ArrayOfTables(ArrayOfTables::new());
+        }
+        let presets = self.doc["preset"].as_array_of_tables_mut().unwrap();
+        let existing = presets
+            .iter_mut()
+            .find(|table| table.get("name").and_then(Item::as_str) == Some(preset.name.as_str()));
+        match existing {
+            Some(table) => *table = preset.to_table(),
+            None => presets.push(preset.to_table()),
+        }
+    }
+
+    /// Atomically replaces `sounds.toml`, rotating the previous contents into
+    /// the backup files first.
+    pub fn save(&self) -> io::Result<()> {
+        let path = Config::path();
+        rotate_backups(&path, BACKUP_COUNT)?;
+        write_atomic(&path, &self.doc.to_string())
+    }
+}
+
+fn invalid_data<E: std::error::Error + Send + Sync + 'static>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".bak.{}", n));
+    path.with_file_name(name)
+}
+
+/// Shifts `file.bak.1 .. file.bak.{count - 1}` up by one, dropping the oldest,
+/// and copies the current file into `file.bak.1`.
+pub fn rotate_backups(path: &Path, count: usize) -> io::Result<()> {
+    if count == 0 || !path.exists() {
+        return Ok(());
+    }
+    for n in (1..count).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, n + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// Writes `contents` to a temporary file next to `path` and renames it into
+/// place, so readers never observe a half-written file.
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    let tmp_path = path.with_file_name(name);
+    let result = (|| {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Runs every migration needed to bring `doc` up to `CONFIG_VERSION` and
+/// returns the version it started at.
+fn migrate(doc: &mut DocumentMut) -> io::Result<u32> {
+    let from_version = match doc.get("version") {
+        None => 0,
+        Some(item) => match item.as_integer().and_then(|v| u32::try_from(v).ok()) {
+            Some(v) => v,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid config version: {}", item),
+                ));
+            }
+        },
+    };
+    if from_version > CONFIG_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} has version {}, but this build only understands up to version {}",
+                CONFIG_FILE, from_version, CONFIG_VERSION
+            ),
+        ));
+    }
+    for step in &MIGRATIONS[from_version as usize..] {
+        step(doc);
+    }
+    doc["version"] = value(CONFIG_VERSION as i64);
+    Ok(from_version)
+}
+
+/// Step to a version whose new keys all have defaults.
+fn added_optional_keys(_doc: &mut DocumentMut) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V0: &str = include_str!("../tests/fixtures/sounds_v0.toml");
+    const V0_MIGRATED: &str = include_str!("../tests/fixtures/sounds_v0_migrated.toml");
+    /// Files written by each version, from 1 to `CONFIG_VERSION`.
+    const VERSIONS: &[&str] = &[
+        include_str!("../tests/fixtures/sounds_v1.toml"),
+        include_str!("../tests/fixtures/sounds_v2.toml"),
+        include_str!("../tests/fixtures/sounds_v3.toml"),
+        include_str!("../tests/fixtures/sounds_v4.toml"),
+        include_str!("../tests/fixtures/sounds_v5.toml"),
+    ];
+
+    fn migrated(contents: &str) -> io::Result<(u32, String)> {
+        let mut doc: DocumentMut = contents.parse().unwrap();
+        let from_version = migrate(&mut doc)?;
+        Ok((from_version, doc.to_string()))
+    }
+
+    #[test]
+    fn v0_gets_a_version() {
+        assert_eq!(migrated(V0).unwrap(), (0, V0_MIGRATED.to_string()));
+
+        let document = ConfigDocument::parse(V0).unwrap();
+        assert_eq!(document.from_version, 0);
+        let config = document.config().unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        let icons: Vec<&str> = config.sound.iter().map(|sound| sound.icon.as_str()).collect();
+        assert_eq!(icons, ["🌧", ""]);
+        assert!(config.preset.is_empty());
+    }
+
+    #[test]
+    fn every_version_has_a_fixture_that_only_gets_a_new_version() {
+        assert_eq!(VERSIONS.len(), CONFIG_VERSION as usize);
+        for (i, contents) in VERSIONS.iter().enumerate() {
+            let version = i as u32 + 1;
+            let expected = contents.replacen(&format!("version = {}", version), &format!("version = {}", CONFIG_VERSION), 1);
+            assert_eq!(migrated(contents).unwrap(), (version, expected), "version {}", version);
+        }
+    }
+
+    #[test]
+    fn current_version_reads_every_key() {
+        let config = ConfigDocument::parse(VERSIONS[CONFIG_VERSION as usize - 1]).unwrap().config().unwrap();
+        let rain = &config.sound[0];
+        assert_eq!(rain.category.as_deref(), Some("Nature"));
+        assert_eq!(rain.tags, ["calm", "water"]);
+        assert!(rain.favorite);
+        assert_eq!(rain.author.as_deref(), Some("Jane Doe"));
+        assert_eq!(rain.license.as_deref(), Some("CC0-1.0"));
+        assert_eq!(rain.source_url.as_deref(), Some("https://example.com/rain"));
+        assert_eq!(config.sound[1], SoundConfig { name: "Storm".to_string(), file: "./sounds/storm.ogg".to_string(), ..SoundConfig::default() });
+        assert_eq!(config.preset[0].name, "Rainy night");
+        assert_eq!(config.preset[0].sounds, BTreeMap::from([("Rain".to_string(), 0.5), ("Storm".to_string(), 0.25)]));
+    }
+
+    #[test]
+    fn migrated_v0_stays_the_same_when_parsed_again() {
+        assert_eq!(migrated(V0_MIGRATED).unwrap(), (CONFIG_VERSION, V0_MIGRATED.to_string()));
+    }
+
+    #[test]
+    fn v0_needs_the_baseline_keys() {
+        let contents = "[[sound]]\nname = 'Storm'\nfile = './sounds/storm.ogg'\n";
+        assert!(ConfigDocument::parse(contents).unwrap().config().is_err());
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        for version in ["6", "-1", "4294967296", "'1'"] {
+            let contents = format!("version = {}\n", version);
+            let error = ConfigDocument::parse(&contents).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "version = {}", version);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Case-insensitive subsequence matching, as used by the `/` search.
+
+/// Returns the char indexes of `text` matched by `pattern` when every char
+/// of the pattern appears in `text` in order. Spaces in the pattern are
+/// ignored and an empty pattern matches everything.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<Vec<usize>> {
+    let mut positions = vec![];
+    let mut text_chars = text.chars().enumerate();
+    for wanted in pattern.chars().filter(|c| !c.is_whitespace()) {
+        let wanted = wanted.to_lowercase().collect::<String>();
+        let (index, _) = text_chars.find(|(_, c)| c.to_lowercase().collect::<String>() == wanted)?;
+        positions.push(index);
+    }
+    Some(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_match_in_order() {
+        assert_eq!(fuzzy_match("rain", "Light Rain"), Some(vec![6, 7, 8, 9]));
+        assert_eq!(fuzzy_match("cfs", "Coffee Shop"), Some(vec![0, 2, 7]));
+        assert_eq!(fuzzy_match("coffee shop", "Coffee Shop"), Some(vec![0, 1, 2, 3, 4, 5, 7, 8, 9, 10]));
+        assert_eq!(fuzzy_match("", "Wind"), Some(vec![]));
+        assert_eq!(fuzzy_match("  ", "Wind"), Some(vec![]));
+    }
+
+    #[test]
+    fn missing_or_reordered_chars_do_not_match() {
+        assert_eq!(fuzzy_match("nr", "Rain"), None);
+        assert_eq!(fuzzy_match("rainy", "Rain"), None);
+        assert_eq!(fuzzy_match("x", "Rain"), None);
+        assert_eq!(fuzzy_match("a", ""), None);
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert_eq!(fuzzy_match("RAIN", "rain"), Some(vec![0, 1, 2, 3]));
+        assert_eq!(fuzzy_match("wInD", "WIND"), Some(vec![0, 1, 2, 3]));
+        assert_eq!(fuzzy_match("ÉTÉ", "Nuit d'été"), Some(vec![7, 8, 9]));
+    }
+
+    #[test]
+    fn indexes_count_chars_not_bytes() {
+        assert_eq!(fuzzy_match("cafe", "Café Terrace"), Some(vec![0, 1, 2, 6]));
+        assert_eq!(fuzzy_match("cat", "Café Terrace"), Some(vec![0, 1, 5]));
+        assert_eq!(fuzzy_match("雨", "小雨 Rain"), Some(vec![1]));
+        assert_eq!(fuzzy_match("r", "🌧 Rain"), Some(vec![2]));
+    }
+}
@@ -0,0 +1,153 @@
+//! Undo and redo for changes to the library and the mixer.
+//!
+//! Instead of recording every kind of action separately, the app captures a
+//! `Snapshot` before and after each key and keeps the pairs that differ.
+//! Undoing restores the state before the change, redoing the state after it.
+
+use crate::{components::sounds_block::MixerEntry, config::SoundConfig, keymap::Action};
+
+/// Number of changes kept in the undo history.
+const HISTORY_LIMIT: usize = 100;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    /// The `[[sound]]` entries of `sounds.toml`, in order, or `None` when the
+    /// file could not be read.
+    pub library: Option<Vec<SoundConfig>>,
+    pub mixer: Vec<MixerEntry>,
+    pub master_volume: f32,
+}
+
+struct Change {
+    before: Snapshot,
+    after: Snapshot,
+    /// Action that made the change, or `None` for text typed into an input.
+    action: Option<Action>,
+}
+
+pub struct History {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+/// Actions that are usually repeated to reach a value, so consecutive ones
+/// are undone as one change.
+fn is_repeatable(action: Action) -> bool {
+    matches!(
+        action,
+        Action::VolumeDown | Action::VolumeUp | Action::MasterVolumeUp | Action::MasterVolumeDown | Action::MoveUp | Action::MoveDown
+    )
+}
+
+impl History {
+    pub fn new() -> Self {
+        History { undo: vec![], redo: vec![] }
+    }
+
+    /// Stores the change made by `action`, if it changed anything.
+    pub fn record(&mut self, before: Snapshot, after: Snapshot, action: Option<Action>) {
+        if before == after {
+            return;
+        }
+        self.redo.clear();
+        if let Some(last) = self.undo.last_mut()
+            && last.action == action
+            && action.is_some_and(is_repeatable)
+            && last.after == before {
+            last.after = after;
+            return;
+        }
+        self.undo.push(Change { before, after, action });
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+    }
+
+    /// Returns the state the change left and the state to go back to, if
+    /// there is anything to undo.
+    pub fn undo(&mut self) -> Option<(Snapshot, Snapshot)> {
+        let change = self.undo.pop()?;
+        let states = (change.after.clone(), change.before.clone());
+        self.redo.push(change);
+        Some(states)
+    }
+
+    /// Returns the state left by undoing the change and the state to go
+    /// forward to, if there is anything to redo.
+    pub fn redo(&mut self) -> Option<(Snapshot, Snapshot)> {
+        let change = self.redo.pop()?;
+        let states = (change.before.clone(), change.after.clone());
+        self.undo.push(change);
+        Some(states)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(master_volume: f32) -> Snapshot {
+        Snapshot { library: None, mixer: vec![], master_volume }
+    }
+
+    fn target(states: Option<(Snapshot, Snapshot)>) -> Option<f32> {
+        states.map(|(_, target)| target.master_volume)
+    }
+
+    #[test]
+    fn changes_are_undone_and_redone_in_order() {
+        let mut history = History::new();
+        history.record(snapshot(0.1), snapshot(0.2), Some(Action::ToggleSound));
+        history.record(snapshot(0.2), snapshot(0.3), Some(Action::ToggleSound));
+        history.record(snapshot(0.3), snapshot(0.3), Some(Action::ToggleSound));
+
+        assert_eq!(history.undo().map(|(current, _)| current.master_volume), Some(0.3));
+        assert_eq!(target(history.undo()), Some(0.1));
+        assert_eq!(target(history.undo()), None);
+        assert_eq!(target(history.redo()), Some(0.2));
+        assert_eq!(target(history.redo()), Some(0.3));
+        assert_eq!(target(history.redo()), None);
+    }
+
+    #[test]
+    fn repeated_actions_are_one_change() {
+        let mut history = History::new();
+        history.record(snapshot(0.1), snapshot(0.2), Some(Action::MasterVolumeUp));
+        history.record(snapshot(0.2), snapshot(0.3), Some(Action::MasterVolumeUp));
+        history.record(snapshot(0.3), snapshot(0.4), Some(Action::ToggleSound));
+        history.record(snapshot(0.4), snapshot(0.5), Some(Action::ToggleSound));
+
+        assert_eq!(target(history.undo()), Some(0.4));
+        assert_eq!(target(history.undo()), Some(0.3));
+        assert_eq!(target(history.undo()), Some(0.1));
+    }
+
+    #[test]
+    fn a_new_change_clears_the_redo_history() {
+        let mut history = History::new();
+        history.record(snapshot(0.1), snapshot(0.2), Some(Action::ToggleSound));
+        history.record(snapshot(0.2), snapshot(0.3), Some(Action::ToggleSound));
+        history.undo();
+        history.record(snapshot(0.2), snapshot(0.5), None);
+
+        assert_eq!(target(history.redo()), None);
+        assert_eq!(target(history.undo()), Some(0.2));
+        assert_eq!(target(history.undo()), Some(0.1));
+    }
+
+    #[test]
+    fn only_the_last_changes_are_kept() {
+        let mut history = History::new();
+        for i in 0..HISTORY_LIMIT + 10 {
+            history.record(snapshot(i as f32), snapshot(i as f32 + 1.0), Some(Action::ToggleSound));
+        }
+        let mut undone = 0;
+        let mut last = None;
+        while let Some(volume) = target(history.undo()) {
+            undone += 1;
+            last = Some(volume);
+        }
+        assert_eq!(undone, HISTORY_LIMIT);
+        assert_eq!(last, Some(10.0));
+    }
+}
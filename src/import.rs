@@ -0,0 +1,179 @@
+use std::{collections::HashSet, fs, io, path::{Path, PathBuf}};
+use crate::{app::resolve_sound_path, components::sound::is_supported_audio, config::{ConfigDocument, SoundConfig}};
+
+/// Icon given to imported sounds whose name matches none of `KEYWORD_ICONS`.
+pub const DEFAULT_ICON: &str = "♪";
+
+/// Keywords looked up in the name of an imported file, in order, to pick the
+/// same icons the bundled sounds use.
+const KEYWORD_ICONS: &[(&str, &str)] = &[
+    ("rain", "\u{e239}"),
+    ("storm", "\u{e208}"),
+    ("thunder", "\u{e208}"),
+    ("wind", "\u{ef16}"),
+    ("fire", "\u{f0e2e}"),
+    ("wave", "\u{f078d}"),
+    ("ocean", "\u{f078d}"),
+    ("sea", "\u{f078d}"),
+    ("stream", "\u{f078d}"),
+    ("river", "\u{f078d}"),
+    ("water", "\u{f078d}"),
+    ("boat", "\u{f0ec8}"),
+    ("ship", "\u{f0ec8}"),
+    ("bird", "\u{f15c6}"),
+    ("train", "\u{f052c}"),
+    ("city", "\u{f0146}"),
+    ("traffic", "\u{f0146}"),
+    ("coffee", "\u{f0176}"),
+    ("cafe", "\u{f0176}"),
+    ("noise", "\u{f147d}"),
+    ("cricket", "\u{f0f65}"),
+    ("night", "\u{f0f65}"),
+];
+
+pub struct ScanResult {
+    /// Sounds found in the directory that are not in the library yet.
+    pub sounds: Vec<SoundConfig>,
+    /// Audio files skipped because the library already points at them.
+    pub already_imported: usize,
+}
+
+/// Recursively collects the supported audio files under `dir` as library
+/// entries, named after their file names.
+pub fn scan_directory(dir: &Path) -> io::Result<ScanResult> {
+    let mut files = vec![];
+    collect_audio_files(dir, &mut files)?;
+    files.sort();
+
+    let known = library_paths();
+    let mut already_imported = 0;
+    let mut sounds = vec![];
+    for file in files {
+        let file = file.canonicalize().unwrap_or(file);
+        if known.contains(&file) {
+            already_imported += 1;
+            continue;
+        }
+        let name = name_from_path(&file);
+        sounds.push(SoundConfig {
+            icon: icon_for_name(&name).to_string(),
+            name,
+            file: file.to_string_lossy().to_string(),
+            ..Default::default()
+        });
+    }
+    Ok(ScanResult { sounds, already_imported })
+}
+
+fn collect_audio_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        // `file_type` does not follow symlinks, so linked directories cannot loop
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            collect_audio_files(&path, files)?;
+        } else if is_supported_audio(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Canonical paths of every file the library already uses.
+fn library_paths() -> HashSet<PathBuf> {
+    let Ok(config) = ConfigDocument::load().and_then(|document| document.config()) else {
+        return HashSet::new();
+    };
+    config
+        .sound
+        .iter()
+        .filter_map(|sound| Path::new(&resolve_sound_path(&sound.file)).canonicalize().ok())
+        .collect()
+}
+
+/// `summer_night-loop.ogg` becomes `Summer Night Loop`.
+pub fn name_from_path(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    stem.split(|c: char| c == '-' || c == '_' || c == '.' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Icon of the first keyword that is a whole word of `name`, or its plural,
+/// so "Train" does not get the icon of rain.
+pub fn icon_for_name(name: &str) -> &'static str {
+    let name = name.to_lowercase();
+    let words: Vec<&str> = name.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).collect();
+    KEYWORD_ICONS
+        .iter()
+        .find(|(keyword, _)| words.iter().any(|word| word == keyword || word.strip_suffix('s') == Some(keyword)))
+        .map_or(DEFAULT_ICON, |(_, icon)| icon)
+}
+
+/// Appends `sounds` to `sounds.toml` in a single write.
+pub fn append_sounds(sounds: &[SoundConfig]) -> io::Result<()> {
+    let mut document = ConfigDocument::load()?;
+    for sound in sounds {
+        document.push_sound(sound);
+    }
+    document.save()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn icon_of(keyword: &str) -> &'static str {
+        KEYWORD_ICONS.iter().find(|(word, _)| *word == keyword).unwrap().1
+    }
+
+    #[test]
+    fn icons_match_whole_words() {
+        assert_eq!(icon_for_name("Light Rain"), icon_of("rain"));
+        assert_eq!(icon_for_name("rain-loop"), icon_of("rain"));
+        assert_eq!(icon_for_name("Brainwave"), DEFAULT_ICON);
+        assert_eq!(icon_for_name("Train"), icon_of("train"));
+        assert_eq!(icon_for_name("Ocean Waves"), icon_of("wave"));
+        assert_eq!(icon_for_name("Distant Storms"), icon_of("storm"));
+        assert_eq!(icon_for_name("Seashore"), DEFAULT_ICON);
+        assert_eq!(icon_for_name(""), DEFAULT_ICON);
+    }
+
+    #[test]
+    fn names_come_from_file_names() {
+        assert_eq!(name_from_path(Path::new("sounds/summer_night-loop.ogg")), "Summer Night Loop");
+        assert_eq!(name_from_path(Path::new("Light Rain.mp3")), "Light Rain");
+        assert_eq!(name_from_path(Path::new("rain..v2.ogg")), "Rain V2");
+        assert_eq!(name_from_path(Path::new("été_à_paris.flac")), "Été À Paris");
+        assert_eq!(name_from_path(Path::new("--.wav")), "");
+    }
+
+    #[test]
+    fn scans_keep_supported_audio_only() {
+        let dir = std::env::temp_dir().join(format!("blanket-tui-scan-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        for file in ["rain.ogg", "Light_Rain.MP3", "notes.txt", "cover.jpg", "rain.ogg.part", ".hidden.ogg", "sub/ocean-waves.flac"] {
+            fs::write(dir.join(file), b"").unwrap();
+        }
+
+        let result = scan_directory(&dir).unwrap();
+        let names: Vec<&str> = result.sounds.iter().map(|sound| sound.name.as_str()).collect();
+        assert_eq!(names, ["Light Rain", "Rain", "Ocean Waves"]);
+        assert_eq!(result.already_imported, 0);
+        assert!(result.sounds.iter().all(|sound| Path::new(&sound.file).is_absolute()));
+        assert_eq!(result.sounds[2].icon, icon_of("wave"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
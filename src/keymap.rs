@@ -0,0 +1,637 @@
+//! Key bindings. Keys are translated into an `Action` for the context that
+//! has focus before any component sees them. The bindings start from a
+//! preset, default or vim, and `keys.toml` can rebind every action.
+
+use std::{fmt, fs, io, path::PathBuf};
+use serde::{Deserialize, Serialize};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crate::app::RESOURCES_PATH;
+
+pub const KEYS_FILE: &str = "keys.toml";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Back,
+    SwitchKeymap,
+    Help,
+    PlayPauseAll,
+    SelectPrevious,
+    SelectNext,
+    SelectFirst,
+    SelectLast,
+    SelectLeft,
+    SelectRight,
+    PageUp,
+    PageDown,
+    VolumeDown,
+    VolumeUp,
+    ToggleSound,
+    MasterVolumeUp,
+    MasterVolumeDown,
+    Delete,
+    MoveUp,
+    MoveDown,
+    Sort,
+    ToggleFavorite,
+    FavoritesOnly,
+    MuteGroup,
+    SoloGroup,
+    AddSound,
+    EditSound,
+    Import,
+    NextPreset,
+    SavePreset,
+    ExportMix,
+    Search,
+    CommandLine,
+    ToggleView,
+    ToggleSpectrum,
+    ToggleInfo,
+    Messages,
+    Undo,
+    Redo,
+    ClosePopup,
+    SubmitPopup,
+    NextInput,
+    BrowseFiles,
+    ClearSearch,
+    ConfirmSearch,
+    RunCommand,
+    CloseCommand,
+    Complete,
+    HistoryPrevious,
+    HistoryNext,
+}
+
+/// Where the keyboard focus is. Each context has its own bindings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Context {
+    List,
+    Popup,
+    Search,
+    Command,
+    Grid,
+}
+
+impl Context {
+    pub const ALL: [Context; 5] = [Context::List, Context::Grid, Context::Popup, Context::Search, Context::Command];
+
+    /// Table name in `keys.toml`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Context::List => "list",
+            Context::Popup => "popup",
+            Context::Search => "search",
+            Context::Command => "command",
+            Context::Grid => "grid",
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Context::List => "Sound list",
+            Context::Popup => "Add / Edit popup",
+            Context::Search => "Search",
+            Context::Command => "Command line",
+            Context::Grid => "Sound grid (other keys as in the list)",
+        }
+    }
+
+    /// Context whose bindings apply when this one does not bind a key.
+    pub fn fallback(self) -> Option<Context> {
+        match self {
+            Context::Grid => Some(Context::List),
+            _ => None,
+        }
+    }
+}
+
+struct ActionInfo {
+    context: Context,
+    action: Action,
+    /// Key in `keys.toml`.
+    name: &'static str,
+    description: &'static str,
+    keys: &'static [&'static str],
+}
+
+const fn info(context: Context, action: Action, name: &'static str, description: &'static str, keys: &'static [&'static str]) -> ActionInfo {
+    ActionInfo { context, action, name, description, keys }
+}
+
+/// Every bindable action with its default keys, in the order help lists them.
+const ACTIONS: &[ActionInfo] = &[
+    info(Context::List, Action::SelectPrevious, "select_previous", "Select the previous row", &["up"]),
+    info(Context::List, Action::SelectNext, "select_next", "Select the next row", &["down"]),
+    info(Context::List, Action::SelectFirst, "select_first", "Select the first row", &["home"]),
+    info(Context::List, Action::SelectLast, "select_last", "Select the last row", &["end"]),
+    info(Context::List, Action::PageUp, "page_up", "Move half a page up", &["pageup"]),
+    info(Context::List, Action::PageDown, "page_down", "Move half a page down", &["pagedown"]),
+    info(Context::List, Action::ToggleSound, "toggle_sound", "Activate the sound, or collapse the category", &["space"]),
+    info(Context::List, Action::VolumeDown, "volume_down", "Decrease the volume of the selection by 0.05", &["left"]),
+    info(Context::List, Action::VolumeUp, "volume_up", "Increase the volume of the selection by 0.05", &["right"]),
+    info(Context::List, Action::PlayPauseAll, "play_pause_all", "Play/Pause all sounds", &["enter"]),
+    info(Context::List, Action::MasterVolumeUp, "master_volume_up", "Increase general volume by 0.05", &["+"]),
+    info(Context::List, Action::MasterVolumeDown, "master_volume_down", "Decrease general volume by 0.05", &["-"]),
+    info(Context::List, Action::MuteGroup, "mute_group", "Mute or unmute the category", &["m"]),
+    info(Context::List, Action::SoloGroup, "solo_group", "Solo the category, or unsolo", &["s"]),
+    info(Context::List, Action::ToggleFavorite, "toggle_favorite", "Mark or unmark the sound as favorite", &["f"]),
+    info(Context::List, Action::FavoritesOnly, "favorites_only", "Show only favorite sounds, or every sound", &["F"]),
+    info(Context::List, Action::MoveUp, "move_up", "Move the sound up", &["["]),
+    info(Context::List, Action::MoveDown, "move_down", "Move the sound down", &["]"]),
+    info(Context::List, Action::ToggleView, "toggle_view", "Switch between the list and the grid", &["v"]),
+    info(Context::List, Action::ToggleSpectrum, "toggle_spectrum", "Show or hide the spectrum of the mix", &["a"]),
+    info(Context::List, Action::Sort, "sort", "Sort by name, then category, then active first", &["o"]),
+    info(Context::List, Action::Search, "search", "Search the sounds", &["/"]),
+    info(Context::List, Action::CommandLine, "command_line", "Type a command", &[":"]),
+    info(Context::List, Action::AddSound, "add_sound", "Add sound", &["n"]),
+    info(Context::List, Action::EditSound, "edit_sound", "Edit selected sound", &["e"]),
+    info(Context::List, Action::Delete, "delete", "Delete selected sound", &["d"]),
+    info(Context::List, Action::Import, "import", "Import a directory of sounds", &["i"]),
+    info(Context::List, Action::NextPreset, "next_preset", "Load the next preset", &["p"]),
+    info(Context::List, Action::SavePreset, "save_preset", "Save the mix over the loaded preset, or as a new one", &["P"]),
+    info(Context::List, Action::ExportMix, "export_mix", "Export the current mix", &["x"]),
+    info(Context::List, Action::ToggleInfo, "toggle_info", "Show or hide the info panel", &["I"]),
+    info(Context::List, Action::Messages, "messages", "Show the log of messages", &["M"]),
+    info(Context::List, Action::Undo, "undo", "Undo the last change", &["u"]),
+    info(Context::List, Action::Redo, "redo", "Redo the last undone change", &["ctrl-r"]),
+    info(Context::List, Action::SwitchKeymap, "switch_keymap", "Switch between the default and vim keys", &["f2"]),
+    info(Context::List, Action::Help, "help", "Show the keys of the focused part", &["?", "f1"]),
+    info(Context::List, Action::Back, "back", "Clear the search, or quit", &["esc"]),
+    info(Context::List, Action::Quit, "quit", "Quit", &["q"]),
+    info(Context::Grid, Action::SelectPrevious, "select_previous", "Select the card above", &["up"]),
+    info(Context::Grid, Action::SelectNext, "select_next", "Select the card below", &["down"]),
+    info(Context::Grid, Action::SelectLeft, "select_left", "Select the card on the left", &["left"]),
+    info(Context::Grid, Action::SelectRight, "select_right", "Select the card on the right", &["right"]),
+    info(Context::Grid, Action::VolumeDown, "volume_down", "Decrease the volume of the selection by 0.05", &[",", "shift-left"]),
+    info(Context::Grid, Action::VolumeUp, "volume_up", "Increase the volume of the selection by 0.05", &[".", "shift-right"]),
+    info(Context::Popup, Action::NextInput, "next_input", "Complete the file path, otherwise switch input", &["tab"]),
+    info(Context::Popup, Action::BrowseFiles, "browse", "Open file browser", &["ctrl-o"]),
+    info(Context::Popup, Action::SubmitPopup, "submit", "Save sound", &["enter"]),
+    info(Context::Popup, Action::ClosePopup, "close", "Close the popup", &["esc"]),
+    info(Context::Popup, Action::Help, "help", "Show the keys of the popup", &["f1"]),
+    info(Context::Search, Action::SelectPrevious, "select_previous", "Select the previous match", &["up"]),
+    info(Context::Search, Action::SelectNext, "select_next", "Select the next match", &["down"]),
+    info(Context::Search, Action::PageUp, "page_up", "Move half a page up", &["pageup"]),
+    info(Context::Search, Action::PageDown, "page_down", "Move half a page down", &["pagedown"]),
+    info(Context::Search, Action::ToggleSound, "toggle_sound", "Activate the selected sound", &["space"]),
+    info(Context::Search, Action::ConfirmSearch, "confirm", "Stop typing and keep the filter", &["enter"]),
+    info(Context::Search, Action::ClearSearch, "clear", "Clear the search", &["esc"]),
+    info(Context::Search, Action::Help, "help", "Show the keys of the search", &["f1"]),
+    info(Context::Command, Action::RunCommand, "run", "Run the command", &["enter"]),
+    info(Context::Command, Action::Complete, "complete", "Complete the command, sound, preset or file name", &["tab"]),
+    info(Context::Command, Action::HistoryPrevious, "history_previous", "Show the previous command", &["up"]),
+    info(Context::Command, Action::HistoryNext, "history_next", "Show the next command", &["down"]),
+    info(Context::Command, Action::CloseCommand, "close", "Close the command line", &["esc"]),
+    info(Context::Command, Action::Help, "help", "Show the keys of the command line", &["f1"]),
+];
+
+/// A key with its modifiers. Shift is folded into the character, so `F`
+/// and `shift-f` are the same chord.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::Char(c.to_uppercase().next().unwrap_or(c))
+            }
+            KeyCode::BackTab => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            code => code,
+        };
+        KeyChord { code, modifiers }
+    }
+
+    pub fn from_event(event: &KeyEvent) -> Self {
+        KeyChord::new(event.code, event.modifiers)
+    }
+
+    /// Parses chords such as `q`, `F`, `ctrl-r`, `shift-up`, `space` or `+`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (prefix, key) = match text.strip_suffix("--") {
+            Some(prefix) => (prefix, "-"),
+            None => match text.rsplit_once('-') {
+                Some((prefix, key)) if !prefix.is_empty() && !key.is_empty() => (prefix, key),
+                _ => ("", text),
+            },
+        };
+        let mut modifiers = KeyModifiers::NONE;
+        for modifier in prefix.split('-').filter(|m| !m.is_empty()) {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier {:?} in {:?}", modifier, text)),
+            };
+        }
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                name => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key {:?}", text)),
+                },
+            },
+        };
+        Ok(KeyChord::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            f.write_str("Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            f.write_str("Alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            f.write_str("Shift-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::PageUp => f.write_str("PageUp"),
+            KeyCode::PageDown => f.write_str("PageDown"),
+            KeyCode::BackTab => f.write_str("Shift-Tab"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// A key or a sequence of keys, such as `g g`, that triggers an action.
+pub type KeySequence = Vec<KeyChord>;
+
+fn parse_sequence(text: &str) -> Result<KeySequence, String> {
+    let sequence = text.split_whitespace().map(KeyChord::parse).collect::<Result<KeySequence, String>>()?;
+    if sequence.is_empty() {
+        return Err("empty key".to_string());
+    }
+    Ok(sequence)
+}
+
+pub fn format_sequence(sequence: &[KeyChord]) -> String {
+    sequence.iter().map(ToString::to_string).collect::<Vec<String>>().join(" ")
+}
+
+/// Sets of bindings the keymap starts from before `keys.toml` is applied.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeymapPreset {
+    Default,
+    Vim,
+}
+
+impl KeymapPreset {
+    pub fn next(self) -> Self {
+        match self {
+            KeymapPreset::Default => KeymapPreset::Vim,
+            KeymapPreset::Vim => KeymapPreset::Default,
+        }
+    }
+
+    /// Bindings replacing the default keys of their action.
+    fn overrides(self) -> &'static [(Context, Action, &'static [&'static str])] {
+        match self {
+            KeymapPreset::Default => &[],
+            KeymapPreset::Vim => VIM_BINDINGS,
+        }
+    }
+}
+
+impl fmt::Display for KeymapPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            KeymapPreset::Default => "default",
+            KeymapPreset::Vim => "vim",
+        })
+    }
+}
+
+const VIM_BINDINGS: &[(Context, Action, &[&str])] = &[
+    (Context::List, Action::SelectPrevious, &["k", "up"]),
+    (Context::List, Action::SelectNext, &["j", "down"]),
+    (Context::List, Action::SelectFirst, &["g g", "home"]),
+    (Context::List, Action::SelectLast, &["G", "end"]),
+    (Context::List, Action::PageUp, &["ctrl-u", "pageup"]),
+    (Context::List, Action::PageDown, &["ctrl-d", "pagedown"]),
+    (Context::List, Action::VolumeDown, &["h", "left"]),
+    (Context::List, Action::VolumeUp, &["l", "right"]),
+    (Context::List, Action::Delete, &["d d"]),
+    (Context::List, Action::ToggleInfo, &["K", "I"]),
+    (Context::Grid, Action::SelectPrevious, &["k", "up"]),
+    (Context::Grid, Action::SelectNext, &["j", "down"]),
+    (Context::Grid, Action::SelectLeft, &["h", "left"]),
+    (Context::Grid, Action::SelectRight, &["l", "right"]),
+    (Context::Grid, Action::VolumeDown, &["H", ","]),
+    (Context::Grid, Action::VolumeUp, &["L", "."]),
+    (Context::Search, Action::SelectPrevious, &["ctrl-k", "up"]),
+    (Context::Search, Action::SelectNext, &["ctrl-j", "down"]),
+];
+
+/// Result of looking up the keys typed so far.
+pub enum Lookup {
+    Action(Action),
+    /// The keys start a longer sequence, so wait for the next key.
+    Prefix,
+    None,
+}
+
+struct Binding {
+    context: Context,
+    keys: KeySequence,
+    action: Action,
+}
+
+pub struct Keymap {
+    preset: KeymapPreset,
+    bindings: Vec<Binding>,
+}
+
+impl Keymap {
+    pub fn from_preset(preset: KeymapPreset) -> Self {
+        let mut keymap = Keymap { preset, bindings: vec![] };
+        for info in ACTIONS {
+            for key in info.keys {
+                let keys = parse_sequence(key).expect("default key bindings parse");
+                keymap.bindings.push(Binding { context: info.context, keys, action: info.action });
+            }
+        }
+        for (context, action, keys) in preset.overrides() {
+            let keys: Vec<KeySequence> = keys.iter().map(|key| parse_sequence(key).expect("preset key bindings parse")).collect();
+            keymap.bind(*context, *action, keys);
+        }
+        keymap
+    }
+
+    pub fn get_preset(&self) -> KeymapPreset {
+        self.preset
+    }
+
+    pub fn path() -> PathBuf {
+        PathBuf::from(RESOURCES_PATH).join(KEYS_FILE)
+    }
+
+    /// The bindings of `preset` with the overrides of `keys.toml` applied.
+    /// Fails when the file is invalid or binds a key to two actions of a
+    /// context.
+    pub fn load(preset: KeymapPreset) -> io::Result<Self> {
+        let contents = match fs::read_to_string(Self::path()) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        Keymap::parse(preset, &contents).map_err(invalid_data)
+    }
+
+    /// The bindings of `preset` with the overrides of `contents`, written
+    /// like `keys.toml`.
+    fn parse(preset: KeymapPreset, contents: &str) -> Result<Self, String> {
+        let mut keymap = Keymap::from_preset(preset);
+        keymap.apply_overrides(contents)?;
+        let conflicts = keymap.conflicts();
+        if !conflicts.is_empty() {
+            return Err(conflicts.join("; "));
+        }
+        Ok(keymap)
+    }
+
+    /// Replaces the keys of `action` in `context`.
+    fn bind(&mut self, context: Context, action: Action, keys: Vec<KeySequence>) {
+        self.bindings.retain(|binding| !(binding.context == context && binding.action == action));
+        for keys in keys {
+            self.bindings.push(Binding { context, keys, action });
+        }
+    }
+
+    /// Each action listed in a context table replaces its keys. An empty list
+    /// unbinds the action.
+    fn apply_overrides(&mut self, contents: &str) -> Result<(), String> {
+        let table: toml::Table = toml::from_str(contents).map_err(|e| e.to_string())?;
+        for (context_name, actions) in &table {
+            let context = Context::ALL
+                .into_iter()
+                .find(|context| context.name() == context_name)
+                .ok_or_else(|| format!("unknown context [{}]", context_name))?;
+            let actions = actions.as_table().ok_or_else(|| format!("[{}] must be a table", context_name))?;
+            for (action_name, keys) in actions {
+                let info = ACTIONS
+                    .iter()
+                    .find(|info| info.context == context && info.name == action_name)
+                    .ok_or_else(|| format!("unknown action {} in [{}]", action_name, context_name))?;
+                let not_keys = || format!("{} in [{}] must be a key or a list of keys", action_name, context_name);
+                let keys: Vec<&str> = match keys {
+                    toml::Value::String(key) => vec![key.as_str()],
+                    toml::Value::Array(keys) => keys.iter().map(|key| key.as_str().ok_or_else(not_keys)).collect::<Result<_, _>>()?,
+                    _ => return Err(not_keys()),
+                };
+                let keys = keys.into_iter().map(parse_sequence).collect::<Result<Vec<KeySequence>, String>>()?;
+                self.bind(context, info.action, keys);
+            }
+        }
+        Ok(())
+    }
+
+    /// Describes every key bound to more than one action of a context, and
+    /// every key that also starts a longer sequence and would hide it.
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = vec![];
+        for (i, a) in self.bindings.iter().enumerate() {
+            for b in &self.bindings[i + 1..] {
+                if a.context != b.context || a.action == b.action {
+                    continue;
+                }
+                let (shorter, longer) = if a.keys.len() <= b.keys.len() { (a, b) } else { (b, a) };
+                if !longer.keys.starts_with(&shorter.keys) {
+                    continue;
+                }
+                let context = a.context;
+                conflicts.push(if shorter.keys.len() == longer.keys.len() {
+                    format!(
+                        "{} is bound to {} and {} in [{}]",
+                        format_sequence(&a.keys), action_name(context, a.action), action_name(context, b.action), context.name()
+                    )
+                } else {
+                    format!(
+                        "{} ({}) hides {} ({}) in [{}]",
+                        format_sequence(&shorter.keys), action_name(context, shorter.action),
+                        format_sequence(&longer.keys), action_name(context, longer.action), context.name()
+                    )
+                });
+            }
+        }
+        conflicts.sort();
+        conflicts
+    }
+
+    /// Looks `keys` up in `context`, then in the context it falls back to.
+    pub fn lookup(&self, context: Context, keys: &[KeyChord]) -> Lookup {
+        let mut prefix = false;
+        for binding in self.bindings.iter().filter(|binding| binding.context == context) {
+            if binding.keys == keys {
+                return Lookup::Action(binding.action);
+            }
+            prefix |= binding.keys.starts_with(keys);
+        }
+        match context.fallback() {
+            _ if prefix => Lookup::Prefix,
+            Some(fallback) => self.lookup(fallback, keys),
+            None => Lookup::None,
+        }
+    }
+
+    pub fn keys_for(&self, context: Context, action: Action) -> Vec<KeySequence> {
+        self.bindings
+            .iter()
+            .filter(|binding| binding.context == context && binding.action == action)
+            .map(|binding| binding.keys.clone())
+            .collect()
+    }
+
+    /// Description and bound keys of every action of `context`, in help order.
+    pub fn help(&self, context: Context) -> Vec<(&'static str, Vec<KeySequence>)> {
+        ACTIONS
+            .iter()
+            .filter(|info| info.context == context)
+            .map(|info| (info.description, self.keys_for(context, info.action)))
+            .collect()
+    }
+}
+
+fn action_name(context: Context, action: Action) -> &'static str {
+    ACTIONS
+        .iter()
+        .find(|info| info.context == context && info.action == action)
+        .map_or("?", |info| info.name)
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(text: &str) -> KeyChord {
+        KeyChord::parse(text).unwrap()
+    }
+
+    fn action(keymap: &Keymap, context: Context, keys: &str) -> Option<Action> {
+        match keymap.lookup(context, &parse_sequence(keys).unwrap()) {
+            Lookup::Action(action) => Some(action),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn chords_parse_names_and_modifiers() {
+        assert_eq!(chord("q"), KeyChord::new(KeyCode::Char('q'), KeyModifiers::NONE));
+        assert_eq!(chord("space"), KeyChord::new(KeyCode::Char(' '), KeyModifiers::NONE));
+        assert_eq!(chord("PageUp"), KeyChord::new(KeyCode::PageUp, KeyModifiers::NONE));
+        assert_eq!(chord("f12"), KeyChord::new(KeyCode::F(12), KeyModifiers::NONE));
+        assert_eq!(chord("ctrl-r"), KeyChord::new(KeyCode::Char('r'), KeyModifiers::CONTROL));
+        assert_eq!(chord("Control-Alt-x"), KeyChord::new(KeyCode::Char('x'), KeyModifiers::CONTROL | KeyModifiers::ALT));
+        assert_eq!(chord("shift-up"), KeyChord::new(KeyCode::Up, KeyModifiers::SHIFT));
+    }
+
+    #[test]
+    fn minus_is_a_key_of_its_own() {
+        let minus = KeyChord::new(KeyCode::Char('-'), KeyModifiers::NONE);
+        assert_eq!(chord("-"), minus);
+        assert_eq!(chord("--"), minus);
+        assert_eq!(chord("ctrl--"), KeyChord::new(KeyCode::Char('-'), KeyModifiers::CONTROL));
+        assert_eq!(chord("+"), KeyChord::new(KeyCode::Char('+'), KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn shift_is_folded_into_characters() {
+        assert_eq!(chord("shift-f"), chord("F"));
+        assert_eq!(KeyChord::new(KeyCode::Char('f'), KeyModifiers::SHIFT), chord("F"));
+        assert_eq!(KeyChord::new(KeyCode::BackTab, KeyModifiers::SHIFT), chord("backtab"));
+        assert_eq!(chord("shift-f").to_string(), "F");
+    }
+
+    #[test]
+    fn bad_chords_are_rejected() {
+        for text in ["hyper-x", "ctrl-", "f13", "f0", "nothing", ""] {
+            assert!(KeyChord::parse(text).is_err(), "{:?}", text);
+        }
+        assert!(parse_sequence("   ").is_err());
+        assert_eq!(parse_sequence("g  g").unwrap(), vec![chord("g"), chord("g")]);
+    }
+
+    #[test]
+    fn presets_have_no_conflicts() {
+        for preset in [KeymapPreset::Default, KeymapPreset::Vim] {
+            assert_eq!(Keymap::from_preset(preset).conflicts(), Vec::<String>::new(), "{}", preset);
+        }
+    }
+
+    #[test]
+    fn overrides_replace_and_unbind_keys() {
+        let keymap = Keymap::parse(KeymapPreset::Default, "[list]\nquit = \"ctrl-q\"\nsearch = []\n").unwrap();
+        assert_eq!(action(&keymap, Context::List, "ctrl-q"), Some(Action::Quit));
+        assert_eq!(action(&keymap, Context::List, "q"), None);
+        assert_eq!(action(&keymap, Context::List, "/"), None);
+        assert_eq!(action(&keymap, Context::Grid, "ctrl-q"), Some(Action::Quit));
+    }
+
+    #[test]
+    fn vim_sequences_wait_for_the_next_key() {
+        let keymap = Keymap::from_preset(KeymapPreset::Vim);
+        assert!(matches!(keymap.lookup(Context::List, &[chord("g")]), Lookup::Prefix));
+        assert_eq!(action(&keymap, Context::List, "g g"), Some(Action::SelectFirst));
+        assert_eq!(action(&keymap, Context::List, "d d"), Some(Action::Delete));
+        assert_eq!(action(&keymap, Context::Grid, "h"), Some(Action::SelectLeft));
+    }
+
+    #[test]
+    fn conflicts_are_reported() {
+        let error = Keymap::parse(KeymapPreset::Default, "[list]\nsort = \"q\"\n").err().unwrap();
+        assert_eq!(error, "q is bound to quit and sort in [list]");
+
+        let error = Keymap::parse(KeymapPreset::Vim, "[list]\nsort = \"g\"\n").err().unwrap();
+        assert_eq!(error, "g (sort) hides g g (select_first) in [list]");
+
+        // The same key in another context is fine
+        assert!(Keymap::parse(KeymapPreset::Default, "[popup]\nclose = \"q\"\n").is_ok());
+    }
+
+    #[test]
+    fn bad_files_are_rejected() {
+        for contents in ["[lists]\nquit = \"q\"", "[list]\nfly = \"q\"", "[list]\nquit = 1", "list = 1", "[list]\nquit = \"hyper-q\""] {
+            assert!(Keymap::parse(KeymapPreset::Default, contents).is_err(), "{:?}", contents);
+        }
+        let error = Keymap::parse(KeymapPreset::Default, "[list]\nquit = [\"q\", 1]").err().unwrap();
+        assert_eq!(error, "quit in [list] must be a key or a list of keys");
+    }
+}
@@ -1,12 +1,60 @@
+mod analysis;
 mod app;
+mod blanket;
+mod cli;
+mod command;
 mod components;
 mod config;
+mod fuzzy;
+mod history;
+mod import;
+mod keymap;
+mod mix;
+mod settings;
+mod theme;
 use std::io;
 use app::App;
+use ratatui::crossterm::{event::{DisableMouseCapture, EnableMouseCapture}, execute};
+use settings::Settings;
+
+/// Leaves raw mode and the alternate screen when dropped, and stops the mouse
+/// capture if it was started, so an error returned early does not leave the
+/// shell unusable.
+struct TerminalGuard {
+    mouse: bool,
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if self.mouse {
+            let _ = execute!(io::stdout(), DisableMouseCapture);
+        }
+        ratatui::restore();
+    }
+}
 
 fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&args);
+    }
+
+    let mouse = Settings::load().map_or(true, |settings| settings.mouse);
     let mut term = ratatui::init();
+    // Without the mouse the app still works, so a terminal that refuses it
+    // only loses clicks
+    let mouse = mouse && execute!(io::stdout(), EnableMouseCapture).is_ok();
+    if mouse {
+        // The hook of ratatui restores the screen on a panic, but knows
+        // nothing of the mouse
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let _ = execute!(io::stdout(), DisableMouseCapture);
+            hook(info);
+        }));
+    }
+    let guard = TerminalGuard { mouse };
     let result = App::default().run(&mut term);
-    ratatui::restore();
+    drop(guard);
     result
 }
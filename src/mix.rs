@@ -0,0 +1,378 @@
+use std::{collections::{BTreeMap, HashSet}, fs::{self, File}, io::{self, Read}, path::{Component, Path, PathBuf}};
+use serde::{Deserialize, Serialize};
+use crate::{
+    app::{resolve_sound_path, RESOURCES_PATH},
+    config::{round_volume, write_atomic, ConfigDocument, PresetConfig, SoundConfig},
+    import::icon_for_name,
+};
+
+/// Value of `format` in every mix document, so other TOML files are rejected.
+pub const MIX_FORMAT: &str = "blanket-tui-mix";
+pub const MIX_VERSION: u32 = 1;
+
+/// Name of the mix document inside a bundle.
+const BUNDLE_MIX_FILE: &str = "mix.toml";
+/// Directory of the audio files inside a bundle.
+const BUNDLE_SOUNDS_DIR: &str = "sounds";
+/// Kept in the resources directory, next to `sounds.toml`.
+const CURRENT_MIX_FILE: &str = "current_mix.toml";
+
+/// A mix that can be shared between libraries. Sounds are matched by `id`
+/// first and by `name` second, and may point at their audio file relative to
+/// the document so the receiver can add sounds they do not have.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MixDocument {
+    pub format: String,
+    pub version: u32,
+    pub name: String,
+    pub master_volume: f64,
+    #[serde(default)]
+    pub sound: Vec<MixSound>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MixSound {
+    pub id: String,
+    pub name: String,
+    pub volume: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// How exported sounds reference their audio files.
+pub enum FileRefs<'a> {
+    None,
+    /// Relative to the directory the document is written to.
+    RelativeTo(&'a Path),
+    /// Inside the `sounds` directory of a bundle.
+    Bundle,
+}
+
+/// Result of turning a mix document back into a preset of this library.
+pub struct MixImport {
+    pub preset: PresetConfig,
+    /// Sounds the library did not have but that the mix carried a file for.
+    pub new_sounds: Vec<SoundConfig>,
+    pub missing: Vec<String>,
+}
+
+/// Ids are the slug of the file name, which is also how GNOME Blanket names
+/// its sounds, so `./sounds/coffee-shop.ogg` is `coffee-shop`.
+pub fn sound_id(file: &str) -> String {
+    slug(&Path::new(file).file_stem().unwrap_or_default().to_string_lossy())
+}
+
+pub fn slug(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
+impl MixDocument {
+    /// Describes `preset`, giving the sounds found in `library` a reference to
+    /// their audio file unless `files` is `FileRefs::None`.
+    pub fn from_preset(preset: &PresetConfig, library: &[SoundConfig], files: FileRefs) -> Self {
+        let mut bundled = BTreeMap::new();
+        let sound = preset
+            .sounds
+            .iter()
+            .map(|(name, volume)| {
+                let library_sound = library.iter().find(|s| &s.name == name);
+                MixSound {
+                    id: library_sound.map_or_else(|| slug(name), |s| sound_id(&s.file)),
+                    name: name.clone(),
+                    volume: round_volume(*volume),
+                    file: library_sound.and_then(|s| file_reference(&s.file, &files, &mut bundled)),
+                }
+            })
+            .collect();
+        MixDocument {
+            format: MIX_FORMAT.to_string(),
+            version: MIX_VERSION,
+            name: preset.name.clone(),
+            master_volume: round_volume(preset.master_volume),
+            sound,
+        }
+    }
+
+    pub fn parse(contents: &str) -> io::Result<Self> {
+        let mix: MixDocument = toml::from_str(contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if mix.format != MIX_FORMAT {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("not a mix document: format is {:?}", mix.format)));
+        }
+        if mix.version > MIX_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("mix version {} is newer than the supported version {}", mix.version, MIX_VERSION),
+            ));
+        }
+        Ok(mix)
+    }
+
+    pub fn to_toml(&self) -> io::Result<String> {
+        toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Maps the mix onto `library`. `base_dir` is where relative `file`
+    /// references are looked up for sounds the library lacks. Fails when a
+    /// reference points outside `base_dir`.
+    pub fn to_preset(&self, library: &[SoundConfig], base_dir: &Path) -> io::Result<MixImport> {
+        let mut preset = PresetConfig {
+            name: self.name.clone(),
+            master_volume: self.master_volume.clamp(0.0, 1.0) as f32,
+            sounds: BTreeMap::new(),
+        };
+        let mut new_sounds: Vec<SoundConfig> = vec![];
+        let mut missing = vec![];
+        for sound in &self.sound {
+            let volume = sound.volume.clamp(0.0, 1.0) as f32;
+            let found = library
+                .iter()
+                .chain(new_sounds.iter())
+                .find(|s| sound_id(&s.file) == sound.id)
+                .or_else(|| library.iter().find(|s| s.name.eq_ignore_ascii_case(&sound.name)));
+            if let Some(found) = found {
+                preset.sounds.insert(found.name.clone(), volume);
+                continue;
+            }
+            let file = match &sound.file {
+                Some(file) => contained_file(base_dir, file)?,
+                None => None,
+            };
+            match file {
+                Some(file) => {
+                    preset.sounds.insert(sound.name.clone(), volume);
+                    new_sounds.push(SoundConfig {
+                        name: sound.name.clone(),
+                        file: file.to_string_lossy().to_string(),
+                        icon: icon_for_name(&sound.name).to_string(),
+                        ..Default::default()
+                    });
+                }
+                None => missing.push(format!("{} ({})", sound.name, sound.id)),
+            }
+        }
+        Ok(MixImport { preset, new_sounds, missing })
+    }
+
+    /// Writes a tar archive holding this document and the audio files of
+    /// its sounds, so the receiver does not need them in their library.
+    pub fn write_bundle(&self, library: &[SoundConfig], path: &Path) -> io::Result<()> {
+        let mut builder = tar::Builder::new(File::create(path)?);
+        let contents = self.to_toml()?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, BUNDLE_MIX_FILE, contents.as_bytes())?;
+        let mut appended = HashSet::new();
+        for sound in &self.sound {
+            let (Some(file), Some(library_sound)) = (&sound.file, library.iter().find(|s| s.name == sound.name)) else {
+                continue;
+            };
+            if !appended.insert(file) {
+                // Another sound of the mix plays the same file
+                continue;
+            }
+            builder.append_path_with_name(resolve_sound_path(&library_sound.file), file)?;
+        }
+        builder.finish()
+    }
+}
+
+/// Files outside the document directory get no reference, since importing
+/// refuses them; a bundle carries them instead. `bundled` holds the names already given to files in a bundle.
+fn file_reference(file: &str, files: &FileRefs, bundled: &mut BTreeMap<String, PathBuf>) -> Option<String> {
+    let path = PathBuf::from(resolve_sound_path(file));
+    match files {
+        FileRefs::None => None,
+        FileRefs::Bundle => Some(bundle_name(path, bundled)),
+        FileRefs::RelativeTo(doc_dir) => {
+            let path = path.canonicalize().unwrap_or(path);
+            let doc_dir = doc_dir.canonicalize().unwrap_or_else(|_| doc_dir.to_path_buf());
+            path.strip_prefix(&doc_dir).ok().map(|path| path.to_string_lossy().to_string())
+        }
+    }
+}
+
+/// Name of `path` inside a bundle. A file whose name is taken by a file from
+/// another directory gets a numeric suffix, like `rain-2.ogg`, so that one
+/// does not overwrite the other when the bundle is unpacked.
+fn bundle_name(path: PathBuf, bundled: &mut BTreeMap<String, PathBuf>) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let extension = path.extension().map_or(String::new(), |ext| format!(".{}", ext.to_string_lossy()));
+    let mut n = 1;
+    loop {
+        let suffix = if n == 1 { String::new() } else { format!("-{}", n) };
+        let name = format!("{}/{}{}{}", BUNDLE_SOUNDS_DIR, stem, suffix, extension);
+        match bundled.get(&name) {
+            Some(existing) if *existing != path => n += 1,
+            Some(_) => return name,
+            None => {
+                bundled.insert(name.clone(), path);
+                return name;
+            }
+        }
+    }
+}
+
+/// The file `file` refers to in `base_dir`, if it exists. References that
+/// are absolute, go up with `..` or lead outside `base_dir` through a link
+/// are refused, so a mix cannot point at arbitrary files.
+fn contained_file(base_dir: &Path, file: &str) -> io::Result<Option<PathBuf>> {
+    let outside = || io::Error::new(io::ErrorKind::InvalidData, format!("{:?} is outside the directory of the mix", file));
+    let relative = Path::new(file);
+    if !relative.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+        return Err(outside());
+    }
+    let path = base_dir.join(relative);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let path = path.canonicalize()?;
+    if !path.starts_with(base_dir.canonicalize()?) {
+        return Err(outside());
+    }
+    Ok(Some(path))
+}
+
+/// Directory bundles are unpacked into, one subdirectory per mix.
+pub fn mixes_dir() -> PathBuf {
+    PathBuf::from(RESOURCES_PATH).join("mixes")
+}
+
+/// The mix playing when the TUI last quit, exported by `export-mix`.
+fn current_mix_path() -> PathBuf {
+    PathBuf::from(RESOURCES_PATH).join(CURRENT_MIX_FILE)
+}
+
+pub fn save_current(preset: &PresetConfig) -> io::Result<()> {
+    let contents = toml::to_string(preset).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    write_atomic(&current_mix_path(), &contents)
+}
+
+pub fn load_current() -> io::Result<PresetConfig> {
+    match fs::read_to_string(current_mix_path()) {
+        Ok(contents) => toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(io::Error::new(io::ErrorKind::NotFound, "no mix was played yet")),
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads a mix from a `.toml` document or a `.tar` bundle. Bundles are
+/// unpacked under `mixes_dir()`, and the returned directory is the one
+/// relative file references should be resolved against.
+pub fn read_mix(path: &Path) -> io::Result<(MixDocument, PathBuf)> {
+    if !is_bundle(path) {
+        let mix = MixDocument::parse(&fs::read_to_string(path)?)?;
+        let base_dir = path.parent().map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+        return Ok((mix, base_dir));
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let target = mixes_dir().join(slug(&stem));
+    fs::create_dir_all(target.join(BUNDLE_SOUNDS_DIR))?;
+    let mut contents = None;
+    let mut archive = tar::Archive::new(File::open(path)?);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.to_path_buf();
+        match entry.header().entry_type() {
+            tar::EntryType::Regular => {}
+            tar::EntryType::Directory => continue,
+            // Links could point anywhere on disk
+            entry_type => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} holds {} as a {:?} entry, only files are allowed", path.display(), entry_path.display(), entry_type),
+                ));
+            }
+        }
+        if entry_path == Path::new(BUNDLE_MIX_FILE) {
+            let mut text = String::new();
+            entry.read_to_string(&mut text)?;
+            contents = Some(text);
+        } else if entry_path.starts_with(BUNDLE_SOUNDS_DIR) {
+            // Only keep the file name so entries cannot escape the target
+            if let Some(name) = entry_path.file_name() {
+                entry.unpack(target.join(BUNDLE_SOUNDS_DIR).join(name))?;
+            }
+        }
+    }
+    let contents = contents.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{} has no {}", path.display(), BUNDLE_MIX_FILE))
+    })?;
+    Ok((MixDocument::parse(&contents)?, target))
+}
+
+/// Adds the result of an import to `sounds.toml` in a single save.
+pub fn apply_import(import: &MixImport) -> io::Result<()> {
+    let mut document = ConfigDocument::load()?;
+    for sound in &import.new_sounds {
+        document.push_sound(sound);
+    }
+    document.set_preset(&import.preset);
+    document.save()
+}
+
+pub fn is_bundle(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("tar"))
+}
+
+/// Writes `preset` as a TOML document, or as a bundle when `path` ends in
+/// `.tar`. Bundles always carry their files. Presets name their sounds, so
+/// a mix of a sound whose name is shared by other sounds is refused rather
+/// than exported with the wrong file.
+pub fn export(preset: &PresetConfig, library: &[SoundConfig], path: &Path, with_files: bool) -> io::Result<()> {
+    if let Some(name) = preset.sounds.keys().find(|name| library.iter().filter(|s| &s.name == *name).count() > 1) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("more than one sound is called {:?}, rename them first", name)));
+    }
+    if is_bundle(path) {
+        return MixDocument::from_preset(preset, library, FileRefs::Bundle).write_bundle(library, path);
+    }
+    let doc_dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let files = if with_files { FileRefs::RelativeTo(doc_dir) } else { FileRefs::None };
+    let mix = MixDocument::from_preset(preset, library, files);
+    write_atomic(path, &mix.to_toml()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_stay_inside_the_mix() {
+        let dir = std::env::temp_dir().join(format!("blanket-tui-mix-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sounds")).unwrap();
+        fs::write(dir.join("sounds/rain.ogg"), b"").unwrap();
+
+        let found = contained_file(&dir, "sounds/rain.ogg").unwrap();
+        assert_eq!(found, Some(dir.join("sounds/rain.ogg").canonicalize().unwrap()));
+        assert_eq!(contained_file(&dir, "./sounds/wind.ogg").unwrap(), None);
+        for file in ["/etc/passwd", "../rain.ogg", "sounds/../../rain.ogg"] {
+            assert!(contained_file(&dir, file).is_err(), "{:?}", file);
+        }
+        #[cfg(unix)]
+        {
+            let outside = dir.with_extension("ogg");
+            fs::write(&outside, b"").unwrap();
+            std::os::unix::fs::symlink(&outside, dir.join("sounds/link.ogg")).unwrap();
+            assert!(contained_file(&dir, "sounds/link.ogg").is_err());
+            fs::remove_file(&outside).unwrap();
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shared_names_are_not_exported() {
+        let sound = |name: &str, file: &str| SoundConfig { name: name.to_string(), file: file.to_string(), ..SoundConfig::default() };
+        let library = [sound("Rain", "rain.ogg"), sound("Rain", "heavy-rain.ogg"), sound("Wind", "wind.ogg")];
+        let preset = PresetConfig { name: "Storm".to_string(), master_volume: 1.0, sounds: BTreeMap::from([("Rain".to_string(), 0.5)]) };
+        let path = std::env::temp_dir().join(format!("blanket-tui-shared-{}.toml", std::process::id()));
+        let error = export(&preset, &library, &path, false).unwrap_err();
+        assert_eq!(error.to_string(), "more than one sound is called \"Rain\", rename them first");
+        assert!(!path.exists());
+    }
+}
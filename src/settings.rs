@@ -0,0 +1,79 @@
+//! User preferences, kept apart from the sound library in `settings.toml`.
+
+use std::{fs, io, path::PathBuf};
+use serde::{Deserialize, Serialize};
+use toml_edit::{value, DocumentMut, Item};
+use crate::{app::RESOURCES_PATH, components::sounds_block::View, keymap::KeymapPreset, theme::{ColorSupport, DEFAULT_THEME}};
+
+pub const SETTINGS_FILE: &str = "settings.toml";
+
+/// Every field has a default, so the file may set only what it changes or
+/// be missing altogether.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Ask before deleting sounds, quitting while sounds play and
+    /// overwriting presets.
+    pub confirm: bool,
+    /// Key bindings to start from, `default` or `vim`.
+    pub keymap: KeymapPreset,
+    /// Capture the mouse. Turning it off lets the terminal select text.
+    pub mouse: bool,
+    /// Show the sounds as a list or as a grid of cards. Saved when `v`
+    /// switches it.
+    pub view: View,
+    /// Name of a theme in `resources/themes/`: `dark`, `light`,
+    /// `high-contrast` or one of the user's.
+    pub theme: String,
+    /// Colors the terminal supports: `auto`, `16`, `256` or `truecolor`.
+    pub colors: ColorSupport,
+    /// Show the level of each playing sound and of the mix.
+    pub meters: bool,
+    /// Show the spectrum of the mix in the header. Saved when `a` switches
+    /// it.
+    pub spectrum: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings { confirm: true, keymap: KeymapPreset::Default, mouse: true, view: View::List, theme: DEFAULT_THEME.to_string(), colors: ColorSupport::Auto, meters: true, spectrum: false }
+    }
+}
+
+impl Settings {
+    pub fn path() -> PathBuf {
+        PathBuf::from(RESOURCES_PATH).join(SETTINGS_FILE)
+    }
+
+    pub fn load() -> io::Result<Self> {
+        match fs::read_to_string(Self::path()) {
+            Ok(contents) => toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Settings::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Switches the view and writes it to the file.
+    pub fn set_view(&mut self, view: View) -> io::Result<()> {
+        self.view = view;
+        Self::write_value("view", value(view.to_string()))
+    }
+
+    /// Shows or hides the spectrum and writes it to the file.
+    pub fn set_spectrum(&mut self, spectrum: bool) -> io::Result<()> {
+        self.spectrum = spectrum;
+        Self::write_value("spectrum", value(spectrum))
+    }
+
+    /// Sets one key of the file, keeping the rest of the file and its
+    /// comments as they are.
+    fn write_value(key: &str, item: Item) -> io::Result<()> {
+        let mut document = match fs::read_to_string(Self::path()) {
+            Ok(contents) => contents.parse::<DocumentMut>().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => DocumentMut::new(),
+            Err(e) => return Err(e),
+        };
+        document[key] = item;
+        fs::write(Self::path(), document.to_string())
+    }
+}
@@ -0,0 +1,358 @@
+//! Colors and styles of the interface, grouped in named slots.
+//!
+//! Themes are TOML files in `resources/themes/`. The bundled `dark`, `light`
+//! and `high-contrast` themes are also built in, so they work without the
+//! files. A theme that leaves out a slot takes it from `dark`. Colors are
+//! brought down to what the terminal supports, and setting `NO_COLOR`
+//! replaces every color with plain text attributes.
+
+use std::{fs, io, path::PathBuf, str::FromStr, sync::OnceLock};
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+use crate::app::RESOURCES_PATH;
+
+pub const THEMES_DIR: &str = "themes";
+pub const DEFAULT_THEME: &str = "dark";
+
+const BUILTIN_THEMES: &[(&str, &str)] = &[
+    ("dark", include_str!("../resources/themes/dark.toml")),
+    ("light", include_str!("../resources/themes/light.toml")),
+    ("high-contrast", include_str!("../resources/themes/high-contrast.toml")),
+];
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Sets the theme used from then on. Only the first call has an effect.
+pub fn init(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+/// The theme set with `init`, or the default one.
+pub fn current() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
+/// Whether the `NO_COLOR` environment variable asks for no colors.
+pub fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// Colors the terminal can show.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ColorSupport {
+    /// Guess from `COLORTERM` and `TERM`.
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "16")]
+    Ansi16,
+    #[serde(rename = "256")]
+    Ansi256,
+    #[serde(rename = "truecolor")]
+    TrueColor,
+}
+
+impl ColorSupport {
+    fn detect() -> Self {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorSupport::TrueColor
+        } else if term.contains("256color") {
+            ColorSupport::Ansi256
+        } else {
+            ColorSupport::Ansi16
+        }
+    }
+
+    /// The closest color the terminal can show.
+    fn adapt(self, color: Color) -> Color {
+        match (self, color) {
+            (ColorSupport::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(rgb_to_256(r, g, b)),
+            (ColorSupport::Ansi16, Color::Rgb(r, g, b)) => nearest_ansi16(r, g, b),
+            (ColorSupport::Ansi16, Color::Indexed(index)) => {
+                let (r, g, b) = indexed_to_rgb(index);
+                nearest_ansi16(r, g, b)
+            }
+            (_, color) => color,
+        }
+    }
+}
+
+/// The standard xterm values of the 16 ANSI colors.
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// Levels of each channel in the 6×6×6 cube of the 256-color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+fn nearest_ansi16(r: u8, g: u8, b: u8) -> Color {
+    ANSI16.iter().min_by_key(|(_, rgb)| distance(*rgb, (r, g, b))).map_or(Color::Reset, |(color, _)| *color)
+}
+
+/// Index of the closest color in the cube or the gray ramp of the 256-color
+/// palette.
+fn rgb_to_256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |v: u8| CUBE_LEVELS.iter().enumerate().min_by_key(|(_, level)| (**level as i32 - v as i32).abs()).map_or(0, |(i, _)| i);
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+    let gray_index = ((r as u32 + g as u32 + b as u32) / 3).saturating_sub(3).min(230) / 10;
+    let gray = 8 + gray_index as u8 * 10;
+    if distance((gray, gray, gray), (r, g, b)) < distance(cube, (r, g, b)) {
+        232 + gray_index as u8
+    } else {
+        16 + 36 * ri as u8 + 6 * gi as u8 + bi as u8
+    }
+}
+
+fn indexed_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..16 => ANSI16[index as usize].1,
+        16..232 => {
+            let i = index - 16;
+            (CUBE_LEVELS[(i / 36) as usize], CUBE_LEVELS[(i / 6 % 6) as usize], CUBE_LEVELS[(i % 6) as usize])
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// A slot as written in a theme file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StyleDef {
+    fg: Option<String>,
+    bg: Option<String>,
+    #[serde(default)]
+    modifiers: Vec<String>,
+}
+
+impl StyleDef {
+    fn to_style(&self) -> Result<Style, String> {
+        let color = |name: &str| Color::from_str(name).map_err(|_| format!("unknown color {:?}", name));
+        let mut style = Style::default();
+        if let Some(fg) = &self.fg {
+            style = style.fg(color(fg)?);
+        }
+        if let Some(bg) = &self.bg {
+            style = style.bg(color(bg)?);
+        }
+        for modifier in &self.modifiers {
+            style = style.add_modifier(match modifier.as_str() {
+                "bold" => Modifier::BOLD,
+                "dim" => Modifier::DIM,
+                "italic" => Modifier::ITALIC,
+                "underlined" => Modifier::UNDERLINED,
+                "reversed" => Modifier::REVERSED,
+                "crossed_out" => Modifier::CROSSED_OUT,
+                _ => return Err(format!("unknown modifier {:?}", modifier)),
+            });
+        }
+        Ok(style)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub inactive: Style,
+    pub active: Style,
+    pub selected: Style,
+    pub muted: Style,
+    pub border: Style,
+    pub focus: Style,
+    pub popup: Style,
+    pub gauge: Style,
+    pub gauge_empty: Style,
+    pub header: Style,
+    pub highlight: Style,
+    pub label: Style,
+    pub error: Style,
+}
+
+impl Theme {
+    pub fn default() -> Self {
+        let mut theme = Theme::monochrome();
+        theme.apply(BUILTIN_THEMES[0].1).expect("the dark theme parses");
+        theme
+    }
+
+    /// Text attributes only, for `NO_COLOR`.
+    pub fn monochrome() -> Self {
+        Theme {
+            inactive: Style::default(),
+            active: Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            selected: Style::default().add_modifier(Modifier::REVERSED),
+            muted: Style::default().add_modifier(Modifier::DIM),
+            border: Style::default(),
+            focus: Style::default().add_modifier(Modifier::BOLD),
+            popup: Style::default(),
+            gauge: Style::default(),
+            gauge_empty: Style::default().add_modifier(Modifier::DIM),
+            header: Style::default().add_modifier(Modifier::BOLD),
+            highlight: Style::default().add_modifier(Modifier::UNDERLINED),
+            label: Style::default().add_modifier(Modifier::DIM),
+            error: Style::default().add_modifier(Modifier::BOLD),
+        }
+    }
+
+    pub fn path(name: &str) -> PathBuf {
+        PathBuf::from(RESOURCES_PATH).join(THEMES_DIR).join(format!("{}.toml", name))
+    }
+
+    /// Loads the theme called `name` from `resources/themes/`, falling back
+    /// to the built-in theme of that name, with its colors adapted to
+    /// `colors`.
+    pub fn load(name: &str, colors: ColorSupport) -> io::Result<Self> {
+        let contents = match fs::read_to_string(Self::path(name)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => match BUILTIN_THEMES.iter().find(|(builtin, _)| *builtin == name) {
+                Some((_, contents)) => contents.to_string(),
+                None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("no theme called {:?}", name))),
+            },
+            Err(e) => return Err(e),
+        };
+        let mut theme = Theme::default();
+        theme.apply(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        theme.adapt(colors);
+        Ok(theme)
+    }
+
+    /// The theme to draw with: `name` adapted to `colors`, or text attributes
+    /// only when `no_color` is set, whichever theme was chosen.
+    pub fn choose(name: &str, colors: ColorSupport, no_color: bool) -> io::Result<Self> {
+        if no_color {
+            return Ok(Theme::monochrome());
+        }
+        Theme::load(name, colors)
+    }
+
+    fn slot_mut(&mut self, name: &str) -> Option<&mut Style> {
+        Some(match name {
+            "inactive" => &mut self.inactive,
+            "active" => &mut self.active,
+            "selected" => &mut self.selected,
+            "muted" => &mut self.muted,
+            "border" => &mut self.border,
+            "focus" => &mut self.focus,
+            "popup" => &mut self.popup,
+            "gauge" => &mut self.gauge,
+            "gauge_empty" => &mut self.gauge_empty,
+            "header" => &mut self.header,
+            "highlight" => &mut self.highlight,
+            "label" => &mut self.label,
+            "error" => &mut self.error,
+            _ => return None,
+        })
+    }
+
+    /// Replaces the slots set in `contents`.
+    fn apply(&mut self, contents: &str) -> Result<(), String> {
+        let table: toml::Table = toml::from_str(contents).map_err(|e| e.to_string())?;
+        for (name, value) in table {
+            let slot = self.slot_mut(&name).ok_or_else(|| format!("unknown slot {:?}", name))?;
+            let def: StyleDef = value.try_into().map_err(|e| format!("{}: {}", name, e))?;
+            *slot = def.to_style().map_err(|e| format!("{}: {}", name, e))?;
+        }
+        Ok(())
+    }
+
+    fn adapt(&mut self, colors: ColorSupport) {
+        let colors = if colors == ColorSupport::Auto { ColorSupport::detect() } else { colors };
+        for style in [
+            &mut self.inactive, &mut self.active, &mut self.selected, &mut self.muted, &mut self.border, &mut self.focus,
+            &mut self.popup, &mut self.gauge, &mut self.gauge_empty, &mut self.header, &mut self.highlight, &mut self.label,
+            &mut self.error,
+        ] {
+            style.fg = style.fg.map(|color| colors.adapt(color));
+            style.bg = style.bg.map(|color| colors.adapt(color));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgb_colors_map_to_the_256_palette() {
+        assert_eq!(rgb_to_256(0, 0, 0), 16);
+        assert_eq!(rgb_to_256(255, 255, 255), 231);
+        assert_eq!(rgb_to_256(255, 0, 0), 196);
+        assert_eq!(rgb_to_256(0, 255, 0), 46);
+        assert_eq!(rgb_to_256(0, 0, 255), 21);
+        assert_eq!(rgb_to_256(95, 135, 175), 67);
+        assert_eq!(rgb_to_256(100, 140, 170), 67);
+        assert_eq!(rgb_to_256(8, 8, 8), 232);
+        assert_eq!(rgb_to_256(128, 128, 128), 244);
+        assert_eq!(rgb_to_256(238, 238, 238), 255);
+    }
+
+    #[test]
+    fn colors_map_to_the_nearest_ansi16() {
+        assert_eq!(nearest_ansi16(0, 0, 0), Color::Black);
+        assert_eq!(nearest_ansi16(200, 10, 0), Color::Red);
+        assert_eq!(nearest_ansi16(250, 10, 10), Color::LightRed);
+        assert_eq!(nearest_ansi16(130, 130, 130), Color::DarkGray);
+        assert_eq!(nearest_ansi16(240, 240, 240), Color::Gray);
+        assert_eq!(nearest_ansi16(90, 90, 250), Color::LightBlue);
+        assert_eq!(ColorSupport::Ansi16.adapt(Color::Indexed(196)), Color::LightRed);
+        assert_eq!(ColorSupport::Ansi256.adapt(Color::Rgb(255, 0, 0)), Color::Indexed(196));
+        assert_eq!(ColorSupport::TrueColor.adapt(Color::Rgb(1, 2, 3)), Color::Rgb(1, 2, 3));
+    }
+
+    #[test]
+    fn builtin_themes_parse() {
+        for (name, contents) in BUILTIN_THEMES {
+            assert!(Theme::default().apply(contents).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn themes_set_only_their_slots() {
+        let mut theme = Theme::default();
+        theme.apply("error = { fg = \"#ff0000\", modifiers = [\"bold\", \"italic\"] }").unwrap();
+        assert_eq!(theme.error, Style::default().fg(Color::Rgb(255, 0, 0)).add_modifier(Modifier::BOLD | Modifier::ITALIC));
+        assert_eq!(theme.label, Theme::default().label);
+    }
+
+    #[test]
+    fn bad_themes_are_rejected() {
+        let error = |contents: &str| Theme::default().apply(contents).unwrap_err();
+        assert_eq!(error("bogus = { fg = \"red\" }"), "unknown slot \"bogus\"");
+        assert_eq!(error("error = { fg = \"reddish\" }"), "error: unknown color \"reddish\"");
+        assert_eq!(error("error = { bg = \"#12345\" }"), "error: unknown color \"#12345\"");
+        assert_eq!(error("error = { modifiers = [\"blink\"] }"), "error: unknown modifier \"blink\"");
+        assert!(error("error = { color = \"red\" }").starts_with("error: "));
+        assert!(Theme::default().apply("error = ").is_err());
+    }
+
+    #[test]
+    fn no_color_wins_over_the_chosen_theme() {
+        assert_eq!(Theme::choose("light", ColorSupport::TrueColor, true).unwrap(), Theme::monochrome());
+        assert_eq!(Theme::choose("missing", ColorSupport::TrueColor, true).unwrap(), Theme::monochrome());
+        assert!(Theme::choose("missing", ColorSupport::TrueColor, false).is_err());
+        assert_ne!(Theme::choose("high-contrast", ColorSupport::TrueColor, false).unwrap(), Theme::monochrome());
+    }
+}